@@ -0,0 +1,19 @@
+/// Reasons for an exceptional halt. All of them consume every unit of gas left in the context.
+///
+/// A CALL or CREATE that cannot start, because it would go past the call depth limit or send
+/// more value than the caller holds, is not one of them: it just pushes 0, and the caller keeps
+/// running with the gas it had set aside for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionError {
+    StackUnderflow,
+    StackOverflow,
+    InvalidOpcode(u8),
+    InvalidJump,
+    OutOfGas,
+    WriteProtection,
+    ReturnDataOutOfBounds,
+    InvalidPrecompileInput,
+    InitCodeSizeExceeded,
+    CodeSizeExceeded,
+    InvalidCodePrefix,
+}
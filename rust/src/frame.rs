@@ -0,0 +1,209 @@
+use std::cmp::min;
+
+use primitive_types::U256;
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    message::Message,
+    operations::{jump_destinations, write_memory, MAX_CODE_SIZE},
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockchainState, Checkpoint},
+    EvmLog, EvmResult,
+};
+
+/// What the parent frame is waiting for while a sub-frame runs.
+pub enum FrameKind {
+    Transaction,
+    Call { ret_offset: usize, ret_size: usize },
+    Create { address: U256 },
+}
+
+/// Why the interpreter loop has to leave the current frame.
+pub enum Interrupt {
+    Enter(Box<Frame>),
+    Halt(Outcome),
+}
+
+/// How a frame stopped executing.
+pub struct Outcome {
+    pub success: bool,
+    pub error: Option<ExecutionError>,
+    pub ret: Option<Vec<u8>>,
+}
+
+impl Outcome {
+    pub fn stop() -> Self {
+        Outcome {
+            success: true,
+            error: None,
+            ret: None,
+        }
+    }
+
+    pub fn exceptional(error: ExecutionError) -> Self {
+        Outcome {
+            success: false,
+            error: Some(error),
+            ret: None,
+        }
+    }
+}
+
+/// Execution state of one call frame: its code, program counter, stack, memory and gas.
+pub struct Frame {
+    pub message: Message,
+    pub code: Vec<u8>,
+    pub jump_destinations: Vec<bool>,
+    pub pc: usize,
+    pub stack: Stack,
+    pub memory: Vec<u8>,
+    pub gas: Gas,
+    pub logs: Vec<EvmLog>,
+    /// Output of the last sub-frame this frame entered.
+    pub return_data: Option<Vec<u8>>,
+    kind: FrameKind,
+    checkpoint: Checkpoint,
+}
+
+impl Frame {
+    /// Sets up a frame running `code`. Every change it makes to the world state after
+    /// `checkpoint` is reverted if it fails.
+    pub fn new(message: Message, code: Vec<u8>, kind: FrameKind, checkpoint: Checkpoint) -> Self {
+        Frame {
+            gas: Gas::new(message.gas),
+            jump_destinations: jump_destinations(&code),
+            message,
+            code,
+            pc: 0,
+            stack: Stack::default(),
+            memory: Vec::new(),
+            logs: Vec::new(),
+            return_data: None,
+            kind,
+            checkpoint,
+        }
+    }
+
+    /// Settles the world state changes of the frame once it has halted.
+    pub fn finish(
+        self,
+        outcome: Outcome,
+        chain_state: &mut BlockchainState,
+        spec: SpecId,
+    ) -> (FrameKind, EvmResult) {
+        let Outcome {
+            mut success,
+            mut error,
+            mut ret,
+        } = outcome;
+        let mut gas = self.gas;
+        let mut logs = self.logs;
+
+        if success {
+            if let FrameKind::Create { address } = self.kind {
+                let code = ret.take().unwrap_or_default();
+                if let Err(err) = deposit_code(chain_state, address, code, &mut gas, spec) {
+                    error = Some(err);
+                }
+            }
+        }
+
+        if error.is_some() {
+            gas.consume_all();
+            success = false;
+            ret = None;
+        }
+
+        if success {
+            chain_state.commit(self.checkpoint);
+        } else {
+            chain_state.revert(self.checkpoint);
+            logs.clear();
+        }
+
+        let result = EvmResult {
+            stack: self.stack.into_vec(),
+            success,
+            error,
+            logs,
+            ret,
+            gas_used: gas.used(),
+            gas_remaining: gas.remaining(),
+            gas_refunded: min(
+                chain_state.refund,
+                gas.used() / gas::max_refund_quotient(spec),
+            ),
+        };
+
+        (self.kind, result)
+    }
+
+    /// Picks up where the frame left off after the sub-frame it entered has finished.
+    pub fn resume(&mut self, kind: FrameKind, result: EvmResult) -> Result<(), ExecutionError> {
+        self.gas.reclaim(result.gas_remaining);
+        if result.success {
+            self.logs.extend(result.logs);
+        }
+
+        match kind {
+            FrameKind::Transaction => Ok(()),
+            FrameKind::Call {
+                ret_offset,
+                ret_size,
+            } => {
+                // the caller only reserved `ret_size` bytes, anything past them is dropped
+                if let Some(ref ret) = result.ret {
+                    let size = min(ret.len(), ret_size);
+                    write_memory(&mut self.memory, ret_offset, &ret[..size]);
+                }
+                self.return_data = result.ret;
+
+                self.stack.push(if result.success {
+                    U256::one()
+                } else {
+                    U256::zero()
+                })
+            }
+            FrameKind::Create { address } => {
+                // a successful init code's output became the contract code, so only a revert
+                // leaves return data behind
+                self.return_data = result.ret;
+
+                self.stack.push(if result.success {
+                    address
+                } else {
+                    U256::zero()
+                })
+            }
+        }
+    }
+}
+
+/// Stores the code returned by init code as the code of the new contract, at 200 gas per byte.
+fn deposit_code(
+    chain_state: &mut BlockchainState,
+    address: U256,
+    code: Vec<u8>,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    if spec.enabled(SpecId::SpuriousDragon) && code.len() > MAX_CODE_SIZE {
+        return Err(ExecutionError::CodeSizeExceeded);
+    }
+    // the 0xEF prefix is reserved for the EVM Object Format (EIP-3541)
+    if spec.enabled(SpecId::London) && code.first() == Some(&0xef) {
+        return Err(ExecutionError::InvalidCodePrefix);
+    }
+
+    let cost = gas::CODE_DEPOSIT * code.len() as u64;
+    // before Homestead, code that cannot be paid for is dropped but the contract is still created
+    if cost > gas.remaining() && !spec.enabled(SpecId::Homestead) {
+        return Ok(());
+    }
+
+    gas.charge(cost)?;
+    chain_state.set_code(address.into(), code);
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use primitive_types::U256;
+
+use crate::error::ExecutionError;
+use crate::opcodes;
+use crate::spec::SpecId;
+
+pub const ZERO: u64 = 0;
+pub const JUMPDEST: u64 = 1;
+pub const BASE: u64 = 2;
+pub const VERY_LOW: u64 = 3;
+pub const LOW: u64 = 5;
+pub const MID: u64 = 8;
+pub const HIGH: u64 = 10;
+pub const EXP: u64 = 10;
+pub const EXP_BYTE: u64 = 50;
+pub const EXP_BYTE_FRONTIER: u64 = 10;
+pub const KECCAK256: u64 = 30;
+pub const KECCAK256_WORD: u64 = 6;
+pub const COPY: u64 = 3;
+pub const MEMORY: u64 = 3;
+pub const QUAD_COEFF_DIV: u64 = 512;
+pub const WARM_STORAGE_READ: u64 = 100;
+pub const ACCOUNT_READ_FRONTIER: u64 = 20;
+pub const ACCOUNT_READ_TANGERINE: u64 = 700;
+pub const BALANCE_TANGERINE: u64 = 400;
+pub const EXTCODEHASH_CONSTANTINOPLE: u64 = 400;
+pub const CALL_FRONTIER: u64 = 40;
+pub const SLOAD_FRONTIER: u64 = 50;
+pub const SLOAD_TANGERINE: u64 = 200;
+pub const SLOAD_ISTANBUL: u64 = 800;
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+pub const COLD_SLOAD: u64 = 2100;
+pub const BLOCKHASH: u64 = 20;
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000 - COLD_SLOAD;
+pub const SSTORE_CLEARS_SCHEDULE: u64 = SSTORE_RESET + ACCESS_LIST_STORAGE_KEY;
+pub const SSTORE_CLEARS_SCHEDULE_FRONTIER: u64 = 15000;
+pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+pub const CALL_VALUE: u64 = 9000;
+pub const CALL_NEW_ACCOUNT: u64 = 25000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+pub const MAX_REFUND_QUOTIENT_FRONTIER: u64 = 2;
+pub const LOG: u64 = 375;
+pub const LOG_TOPIC: u64 = 375;
+pub const LOG_DATA: u64 = 8;
+pub const CREATE: u64 = 32000;
+pub const CODE_DEPOSIT: u64 = 200;
+pub const INITCODE_WORD: u64 = 2;
+pub const SELFDESTRUCT: u64 = 5000;
+pub const SELFDESTRUCT_REFUND: u64 = 24000;
+pub const ECRECOVER: u64 = 3000;
+pub const SHA256: u64 = 60;
+pub const SHA256_WORD: u64 = 12;
+pub const RIPEMD160: u64 = 600;
+pub const RIPEMD160_WORD: u64 = 120;
+pub const IDENTITY: u64 = 15;
+pub const IDENTITY_WORD: u64 = 3;
+pub const MODEXP_MIN: u64 = 200;
+pub const MODEXP_DIVISOR: u64 = 3;
+pub const MODEXP_DIVISOR_BYZANTIUM: u64 = 20;
+pub const ECADD: u64 = 150;
+pub const ECADD_BYZANTIUM: u64 = 500;
+pub const ECMUL: u64 = 6000;
+pub const ECMUL_BYZANTIUM: u64 = 40000;
+pub const ECPAIRING: u64 = 45000;
+pub const ECPAIRING_BYZANTIUM: u64 = 100000;
+pub const ECPAIRING_PAIR: u64 = 34000;
+pub const ECPAIRING_PAIR_BYZANTIUM: u64 = 80000;
+pub const BLAKE2F_ROUND: u64 = 1;
+pub const POINT_EVALUATION: u64 = 50000;
+pub const BLS12_G1ADD: u64 = 375;
+pub const BLS12_G1MSM: u64 = 12000;
+pub const BLS12_G2ADD: u64 = 600;
+pub const BLS12_G2MSM: u64 = 22500;
+pub const BLS12_PAIRING: u64 = 37700;
+pub const BLS12_PAIRING_PAIR: u64 = 32600;
+pub const BLS12_MAP_FP_TO_G1: u64 = 5500;
+pub const BLS12_MAP_FP2_TO_G2: u64 = 23800;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Gas {
+    limit: u64,
+    remaining: u64,
+}
+
+impl Gas {
+    pub fn new(limit: u64) -> Self {
+        Gas {
+            limit,
+            remaining: limit,
+        }
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    pub fn used(&self) -> u64 {
+        self.limit - self.remaining
+    }
+
+    pub fn charge(&mut self, amount: u64) -> Result<(), ExecutionError> {
+        if amount > self.remaining {
+            self.remaining = 0;
+            return Err(ExecutionError::OutOfGas);
+        }
+
+        self.remaining -= amount;
+        Ok(())
+    }
+
+    /// Hands `requested` gas to a sub-context, capped at all but one 64th of what is left
+    /// (EIP-150) so that the current context can always finish after the sub-context returns.
+    pub fn forward(&mut self, requested: U256) -> u64 {
+        let available = self.remaining - self.remaining / 64;
+        let amount = if requested < available.into() {
+            requested.as_u64()
+        } else {
+            available
+        };
+
+        self.remaining -= amount;
+        amount
+    }
+
+    /// Hands exactly `requested` gas to a sub-context, failing if there is not that much left,
+    /// as was done before EIP-150.
+    pub fn forward_exact(&mut self, requested: U256) -> Result<u64, ExecutionError> {
+        let amount = if requested > self.remaining.into() {
+            u64::MAX
+        } else {
+            requested.as_u64()
+        };
+
+        self.charge(amount)?;
+        Ok(amount)
+    }
+
+    /// Gives back gas that was forwarded to a sub-context and not spent there.
+    pub fn reclaim(&mut self, amount: u64) {
+        self.remaining += amount;
+    }
+
+    /// Exceptional halts burn everything that was left in the context.
+    pub fn consume_all(&mut self) {
+        self.remaining = 0;
+    }
+}
+
+/// Number of 32-byte words needed to hold `size` bytes.
+pub fn words(size: usize) -> u64 {
+    size.div_ceil(32) as u64
+}
+
+/// Total cost of a memory of `words` words, which grows quadratically past 724 bytes.
+pub fn memory_cost(words: u64) -> u64 {
+    MEMORY * words + words * words / QUAD_COEFF_DIV
+}
+
+/// Extra cost of touching an account for the first time in the transaction (EIP-2929).
+pub fn account_access_cost(is_cold: bool, spec: SpecId) -> u64 {
+    if is_cold && spec.enabled(SpecId::Berlin) {
+        COLD_ACCOUNT_ACCESS - WARM_STORAGE_READ
+    } else {
+        ZERO
+    }
+}
+
+/// Cost of SLOAD, and of an SSTORE that changes nothing once net metering applies (EIP-2200).
+/// Since Berlin, this is only the warm part of it.
+pub fn sload_cost(spec: SpecId) -> u64 {
+    if spec.enabled(SpecId::Berlin) {
+        WARM_STORAGE_READ
+    } else if spec.enabled(SpecId::Istanbul) {
+        SLOAD_ISTANBUL
+    } else if spec.enabled(SpecId::TangerineWhistle) {
+        SLOAD_TANGERINE
+    } else {
+        SLOAD_FRONTIER
+    }
+}
+
+/// Cost of an SSTORE overwriting a non-zero value. Berlin moves part of it to the cold access.
+pub fn sstore_reset_cost(spec: SpecId) -> u64 {
+    if spec.enabled(SpecId::Berlin) {
+        SSTORE_RESET
+    } else {
+        SSTORE_RESET + COLD_SLOAD
+    }
+}
+
+/// Refund for clearing a storage slot, cut by EIP-3529.
+pub fn sstore_clears_schedule(spec: SpecId) -> u64 {
+    if spec.enabled(SpecId::London) {
+        SSTORE_CLEARS_SCHEDULE
+    } else {
+        SSTORE_CLEARS_SCHEDULE_FRONTIER
+    }
+}
+
+/// Share of the gas used that refunds can pay back, cut by EIP-3529.
+pub fn max_refund_quotient(spec: SpecId) -> u64 {
+    if spec.enabled(SpecId::London) {
+        MAX_REFUND_QUOTIENT
+    } else {
+        MAX_REFUND_QUOTIENT_FRONTIER
+    }
+}
+
+/// Cost of the opcodes reading other accounts. Since Berlin, this is only the warm part of it
+/// and cold accesses pay the rest (EIP-2929).
+fn account_read_cost(opcode: u8, spec: SpecId) -> u64 {
+    if spec.enabled(SpecId::Berlin) {
+        return WARM_STORAGE_READ;
+    }
+
+    let tangerine = spec.enabled(SpecId::TangerineWhistle);
+    let istanbul = spec.enabled(SpecId::Istanbul);
+    match opcode {
+        // EIP-1884 raised these to the price of the other account reads
+        opcodes::BALANCE | opcodes::EXTCODEHASH if istanbul => ACCOUNT_READ_TANGERINE,
+        opcodes::BALANCE if tangerine => BALANCE_TANGERINE,
+        opcodes::EXTCODEHASH => EXTCODEHASH_CONSTANTINOPLE,
+        opcodes::BALANCE | opcodes::EXTCODESIZE | opcodes::EXTCODECOPY if !tangerine => {
+            ACCOUNT_READ_FRONTIER
+        }
+        opcodes::EXTCODESIZE | opcodes::EXTCODECOPY => ACCOUNT_READ_TANGERINE,
+        _ if tangerine => ACCOUNT_READ_TANGERINE,
+        _ => CALL_FRONTIER,
+    }
+}
+
+/// Cost charged for an opcode before it is executed, independently of its operands.
+pub fn static_cost(opcode: u8, spec: SpecId) -> u64 {
+    match opcode {
+        opcodes::STOP | opcodes::RETURN | opcodes::REVERT => ZERO,
+        opcodes::JUMPDEST => JUMPDEST,
+        opcodes::ADDRESS
+        | opcodes::ORIGIN
+        | opcodes::CALLER
+        | opcodes::CALLVALUE
+        | opcodes::CALLDATASIZE
+        | opcodes::CODESIZE
+        | opcodes::GASPRICE
+        | opcodes::RETURNDATASIZE
+        | opcodes::COINBASE
+        | opcodes::TIMESTAMP
+        | opcodes::NUMBER
+        | opcodes::PREVRANDAO
+        | opcodes::GASLIMIT
+        | opcodes::CHAINID
+        | opcodes::BASEFEE
+        | opcodes::BLOBBASEFEE
+        | opcodes::POP
+        | opcodes::PC
+        | opcodes::MSIZE
+        | opcodes::GAS
+        | opcodes::PUSH0 => BASE,
+        opcodes::ADD
+        | opcodes::SUB
+        | opcodes::LT
+        | opcodes::GT
+        | opcodes::SLT
+        | opcodes::SGT
+        | opcodes::EQ
+        | opcodes::ISZERO
+        | opcodes::AND
+        | opcodes::OR
+        | opcodes::XOR
+        | opcodes::NOT
+        | opcodes::BYTE
+        | opcodes::SHL
+        | opcodes::SHR
+        | opcodes::SAR
+        | opcodes::CALLDATALOAD
+        | opcodes::CALLDATACOPY
+        | opcodes::CODECOPY
+        | opcodes::RETURNDATACOPY
+        | opcodes::MLOAD
+        | opcodes::MSTORE
+        | opcodes::MSTORE8
+        | opcodes::MCOPY
+        | opcodes::BLOBHASH
+        | opcodes::PUSH1..=opcodes::PUSH32
+        | opcodes::DUP1..=opcodes::DUP16
+        | opcodes::SWAP1..=opcodes::SWAP16 => VERY_LOW,
+        opcodes::MUL
+        | opcodes::DIV
+        | opcodes::SDIV
+        | opcodes::MOD
+        | opcodes::SMOD
+        | opcodes::SIGNEXTEND
+        | opcodes::SELFBALANCE => LOW,
+        opcodes::ADDMOD | opcodes::MULMOD | opcodes::JUMP => MID,
+        opcodes::JUMPI => HIGH,
+        opcodes::EXP => EXP,
+        opcodes::KECCAK256 => KECCAK256,
+        opcodes::TLOAD | opcodes::TSTORE => WARM_STORAGE_READ,
+        opcodes::SLOAD => sload_cost(spec),
+        opcodes::BALANCE
+        | opcodes::EXTCODESIZE
+        | opcodes::EXTCODECOPY
+        | opcodes::EXTCODEHASH
+        | opcodes::CALL
+        | opcodes::CALLCODE
+        | opcodes::DELEGATECALL
+        | opcodes::STATICCALL => account_read_cost(opcode, spec),
+        opcodes::BLOCKHASH => BLOCKHASH,
+        opcodes::LOG0..=opcodes::LOG4 => LOG + LOG_TOPIC * (opcode - opcodes::LOG0) as u64,
+        opcodes::CREATE | opcodes::CREATE2 => CREATE,
+        opcodes::SELFDESTRUCT if spec.enabled(SpecId::TangerineWhistle) => SELFDESTRUCT,
+        _ => ZERO,
+    }
+}
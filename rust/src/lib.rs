@@ -1,12 +1,24 @@
+pub mod error;
+mod frame;
+mod gas;
 mod helpers;
+pub mod message;
 mod opcodes;
 mod operations;
+pub mod precompiles;
+pub mod spec;
+mod stack;
 pub mod state;
 
-use std::collections::HashMap;
+use std::mem;
 
-use operations::{write_memory, ContextType};
+use error::ExecutionError;
+use frame::{Frame, FrameKind, Interrupt, Outcome};
+use message::Message;
+use operations::ContextType;
+use precompiles::{Precompile, Precompiles};
 use primitive_types::U256;
+use spec::SpecId;
 use state::BlockchainState;
 
 #[derive(Debug, serde::Deserialize, PartialEq, Eq)]
@@ -20,225 +32,494 @@ pub struct EvmResult {
     pub stack: Vec<U256>,
     pub logs: Vec<EvmLog>,
     pub success: bool,
+    pub error: Option<ExecutionError>,
     pub ret: Option<Vec<u8>>,
+    pub gas_used: u64,
+    pub gas_remaining: u64,
+    pub gas_refunded: u64,
 }
 
+/// The interpreter, along with the hardfork whose rules it follows and the precompiled
+/// contracts it dispatches calls to.
+#[derive(Default)]
+pub struct Evm {
+    spec: SpecId,
+    precompiles: Precompiles,
+}
+
+impl Evm {
+    /// An interpreter following the Cancun rules, with only the standard precompiles.
+    pub fn new() -> Self {
+        Evm::default()
+    }
+
+    /// An interpreter following the rules of `spec`, with the precompiles that existed then.
+    pub fn with_spec(spec: SpecId) -> Self {
+        Evm {
+            spec,
+            precompiles: Precompiles::new(spec),
+        }
+    }
+
+    pub fn spec(&self) -> SpecId {
+        self.spec
+    }
+
+    /// Installs a native contract at `address`, which may replace a standard precompile.
+    pub fn register_precompile(
+        &mut self,
+        address: U256,
+        precompile: impl Precompile + 'static,
+    ) -> Option<Box<dyn Precompile>> {
+        self.precompiles.register(address, precompile)
+    }
+
+    /// Runs `code` as the transaction described by `message`.
+    pub fn run(
+        &self,
+        code: &[u8],
+        memory: &mut Vec<u8>,
+        chain_state: &mut BlockchainState,
+        message: &Message,
+    ) -> EvmResult {
+        self.start(code, memory, chain_state, message).finish()
+    }
+
+    /// Sets up `code` to run as the transaction described by `message`, without executing any
+    /// of it yet. `memory` is handed back once the transaction halts.
+    pub fn start<'a>(
+        &'a self,
+        code: &[u8],
+        memory: &'a mut Vec<u8>,
+        chain_state: &'a mut BlockchainState,
+        message: &Message,
+    ) -> Execution<'a> {
+        // the sender, recipient and precompiled contracts start out warm (EIP-2929), and so
+        // does the coinbase from Shanghai (EIP-3651)
+        let coinbase = chain_state
+            .block
+            .coinbase
+            .filter(|_| self.spec.enabled(SpecId::Shanghai));
+        let accounts = [
+            chain_state.tx.origin,
+            chain_state.tx.from,
+            chain_state.tx.to,
+            coinbase,
+        ];
+        for address in accounts.into_iter().flatten() {
+            chain_state.warm_address(address);
+        }
+        for address in self.precompiles.addresses() {
+            chain_state.warm_address(address.into());
+        }
+
+        // a failed transaction leaves no trace in the world state
+        let checkpoint = chain_state.checkpoint();
+        let mut root = Frame::new(
+            message.clone(),
+            code.to_vec(),
+            FrameKind::Transaction,
+            checkpoint,
+        );
+        root.memory = mem::take(memory);
+
+        Execution {
+            evm: self,
+            memory,
+            chain_state,
+            frames: vec![root],
+            result: None,
+        }
+    }
+}
+
+/// A transaction in progress, which can be run one instruction at a time and left paused in
+/// between.
+pub struct Execution<'a> {
+    evm: &'a Evm,
+    memory: &'a mut Vec<u8>,
+    chain_state: &'a mut BlockchainState,
+    /// Frames entered and not yet halted, the running one last.
+    frames: Vec<Frame>,
+    result: Option<EvmResult>,
+}
+
+impl Execution<'_> {
+    /// Executes the next instruction, returning whether the transaction has halted.
+    pub fn step(&mut self) -> bool {
+        let Some(frame) = self.frames.last_mut() else {
+            return true;
+        };
+        let spec = self.evm.spec;
+
+        let mut outcome = match step(frame, self.chain_state, spec) {
+            None => return false,
+            Some(Interrupt::Enter(mut sub_frame)) => {
+                // precompiled contracts halt as soon as they are entered
+                let precompiled = self.evm.precompiles.execute(&mut sub_frame);
+                self.frames.push(*sub_frame);
+                match precompiled {
+                    Some(outcome) => outcome,
+                    None => return false,
+                }
+            }
+            Some(Interrupt::Halt(outcome)) => outcome,
+        };
+
+        // hand the result of the halted frame to its parent, which may halt in turn
+        while let Some(mut finished) = self.frames.pop() {
+            let Some(parent) = self.frames.last_mut() else {
+                *self.memory = mem::take(&mut finished.memory);
+                let (_, result) = finished.finish(outcome, self.chain_state, spec);
+                self.chain_state.end_transaction();
+                self.result = Some(result);
+                return true;
+            };
+
+            let (kind, result) = finished.finish(outcome, self.chain_state, spec);
+            match parent.resume(kind, result) {
+                Ok(()) => break,
+                Err(err) => outcome = Outcome::exceptional(err),
+            }
+        }
+
+        false
+    }
+
+    /// Runs the rest of the transaction and returns its result.
+    pub fn finish(mut self) -> EvmResult {
+        while !self.step() {}
+
+        let Some(result) = self.result else {
+            unreachable!("the transaction frame only halts after storing its result");
+        };
+        result
+    }
+
+    /// Position of the next instruction in the code of the running frame, if any is running.
+    pub fn pc(&self) -> Option<usize> {
+        self.frames.last().map(|frame| frame.pc)
+    }
+
+    /// How many frames the running one is nested in, the transaction being at depth 0.
+    pub fn depth(&self) -> Option<usize> {
+        self.frames.last().map(|frame| frame.message.depth)
+    }
+
+    /// Gas left to the running frame.
+    pub fn gas_remaining(&self) -> Option<u64> {
+        self.frames.last().map(|frame| frame.gas.remaining())
+    }
+
+    /// Stack of the running frame, from the top down.
+    pub fn stack(&self) -> Option<Vec<U256>> {
+        self.frames.last().map(|frame| frame.stack.to_vec())
+    }
+}
+
+/// Runs `code` with the standard precompiles, see [`Evm::run`].
 pub fn evm(
-    code: &Vec<u8>,
+    code: &[u8],
     memory: &mut Vec<u8>,
-    storage: &mut HashMap<U256, U256>,
     chain_state: &mut BlockchainState,
-    is_static: bool,
+    message: &Message,
 ) -> EvmResult {
-    let mut stack: Vec<U256> = Vec::new();
-    let mut pc = 0;
-    let mut success = true;
-    let code_length = code.len();
-    let mut logs = Vec::<EvmLog>::new();
-    let mut ret: Option<Vec<u8>> = None;
-    let mut last_context_ret: Option<Vec<u8>> = None;
-
-    while pc < code_length {
-        let opcode = code[pc];
-        pc += 1;
-
-        match opcode {
-            opcodes::STOP => break,
-            opcodes::PUSH0..=opcodes::PUSH32 => {
-                let n_bytes = opcode - opcodes::PUSH0;
-                operations::push(pc, n_bytes as usize, code, &mut stack);
-                pc += n_bytes as usize;
-            }
-            opcodes::POP => _ = stack.pop(),
-            opcodes::ADD => operations::add(&mut stack),
-            opcodes::MUL => operations::mul(&mut stack),
-            opcodes::SUB => operations::sub(&mut stack),
-            opcodes::DIV => operations::div(&mut stack),
-            opcodes::SDIV => operations::signed_div(&mut stack),
-            opcodes::MOD => operations::modulo(&mut stack),
-            opcodes::SMOD => operations::signed_modulo(&mut stack),
-            opcodes::ADDMOD => operations::addmod(&mut stack),
-            opcodes::MULMOD => operations::mulmod(&mut stack),
-            opcodes::EXP => operations::exp(&mut stack),
-            opcodes::SIGNEXTEND => operations::signextend(&mut stack),
-            opcodes::LT => operations::less_than(&mut stack),
-            opcodes::GT => operations::greater_than(&mut stack),
-            opcodes::SLT => operations::signed_less_than(&mut stack),
-            opcodes::SGT => operations::signed_greater_than(&mut stack),
-            opcodes::EQ => operations::equal(&mut stack),
-            opcodes::ISZERO => operations::is_zero(&mut stack),
-            opcodes::AND => operations::and(&mut stack),
-            opcodes::OR => operations::or(&mut stack),
-            opcodes::XOR => operations::xor(&mut stack),
-            opcodes::NOT => operations::not(&mut stack),
-            opcodes::KECCAK256 => operations::keccak256(&mut stack, memory),
-            opcodes::BYTE => operations::byte(&mut stack),
-            opcodes::SHL => operations::shl(&mut stack),
-            opcodes::SHR => operations::shr(&mut stack),
-            opcodes::SAR => operations::sar(&mut stack),
-            opcodes::DUP1..=opcodes::DUP16 => {
-                let offset = opcode - opcodes::DUP1 + 1;
-                operations::duplicate(offset as usize, &mut stack);
-            }
-            opcodes::SWAP1..=opcodes::SWAP16 => {
-                let offset = opcode - opcodes::SWAP1 + 1;
-                operations::swap(offset as usize, &mut stack);
-            }
-            opcodes::PC => stack.push((pc - 1).into()),
-            opcodes::GAS => stack.push(U256::MAX),
-            opcodes::JUMP => {
-                let res = operations::jump(&mut stack, code, &mut pc);
-                if res.is_err() {
-                    success = false;
-                    break;
-                }
-            }
-            opcodes::JUMPI => {
-                let res = operations::jump_if(&mut stack, code, &mut pc);
-                if res.is_err() {
-                    success = false;
-                    break;
-                }
-            }
-            opcodes::MSIZE => operations::memsize(&mut stack, memory),
-            opcodes::MSTORE => operations::memstore(&mut stack, memory),
-            opcodes::MSTORE8 => operations::memstore8(&mut stack, memory),
-            opcodes::MLOAD => operations::memload(&mut stack, memory),
-            opcodes::ADDRESS => stack.push(chain_state.tx.to.unwrap().into()),
-            opcodes::CALLER => stack.push(chain_state.tx.from.unwrap().into()),
-            opcodes::ORIGIN => stack.push(chain_state.tx.origin.unwrap().into()),
-            opcodes::GASPRICE => stack.push(chain_state.tx.gasprice.unwrap().into()),
-            opcodes::BASEFEE => stack.push(chain_state.block.basefee.unwrap().into()),
-            opcodes::COINBASE => stack.push(chain_state.block.coinbase.unwrap().into()),
-            opcodes::TIMESTAMP => stack.push(chain_state.block.timestamp.unwrap().into()),
-            opcodes::NUMBER => stack.push(chain_state.block.number.unwrap().into()),
-            opcodes::DIFFICULTY => stack.push(chain_state.block.difficulty.unwrap().into()),
-            opcodes::GASLIMIT => stack.push(chain_state.block.gaslimit.unwrap().into()),
-            opcodes::CHAINID => stack.push(chain_state.block.chainid.unwrap().into()),
-            opcodes::CALLVALUE => operations::call_value(&mut stack, chain_state),
-            opcodes::CALLDATALOAD => operations::call_data_load(&mut stack, chain_state),
-            opcodes::CALLDATASIZE => operations::call_data_size(&mut stack, chain_state),
-            opcodes::CALLDATACOPY => operations::call_data_copy(&mut stack, memory, chain_state),
-            opcodes::CODESIZE => stack.push(code.len().into()),
-            opcodes::CODECOPY => operations::code_copy(&mut stack, memory, code),
-            opcodes::EXTCODESIZE => operations::external_code_size(&mut stack, chain_state),
-            opcodes::EXTCODECOPY => operations::external_code_copy(&mut stack, memory, chain_state),
-            opcodes::EXTCODEHASH => operations::external_code_hash(&mut stack, chain_state),
-            opcodes::BALANCE => operations::get_balance(&mut stack, chain_state),
-            opcodes::SELFBALANCE => operations::self_balance(&mut stack, chain_state),
-            opcodes::SLOAD => operations::storage_load(&mut stack, storage),
-            opcodes::SSTORE => {
-                if is_static {
-                    let return_value = operations::revert_context(&mut stack, memory);
-                    ret = Some(return_value);
-                    success = false;
-                    break;
-                }
+    Evm::new().run(code, memory, chain_state, message)
+}
 
-                operations::storage_store(&mut stack, storage)
-            }
-            opcodes::LOG0..=opcodes::LOG4 => {
-                if is_static {
-                    let return_value = operations::revert_context(&mut stack, memory);
-                    ret = Some(return_value);
-                    success = false;
-                    break;
-                }
+/// Executes the next instruction of `frame`, reporting whether it halted or entered a sub-frame.
+fn step(frame: &mut Frame, chain_state: &mut BlockchainState, spec: SpecId) -> Option<Interrupt> {
+    let Frame {
+        message,
+        code,
+        jump_destinations,
+        pc,
+        stack,
+        memory,
+        gas,
+        logs,
+        return_data: last_context_ret,
+        ..
+    } = frame;
+    let is_static = message.is_static;
 
-                let n_topics = opcode - opcodes::LOG0;
-                let log = operations::log(n_topics, &mut stack, memory, chain_state);
-                logs.push(log);
-            }
-            opcodes::RETURN => {
-                let return_value = operations::return_value(&mut stack, memory);
-                ret = Some(return_value);
-            }
-            opcodes::REVERT => {
-                let return_value = operations::revert_context(&mut stack, memory);
-                ret = Some(return_value);
-                success = false;
-                break;
-            }
-            opcodes::CALL => {
-                last_context_ret = operations::call_context(
-                    &mut stack,
-                    memory,
-                    chain_state,
-                    ContextType::Writeable,
-                    is_static,
-                );
-            }
-            opcodes::DELEGATECALL => {
-                last_context_ret = operations::call_context(
-                    &mut stack,
-                    memory,
-                    chain_state,
-                    ContextType::WriteableDelegate(storage),
-                    is_static,
-                );
-            }
-            opcodes::STATICCALL => {
-                last_context_ret = operations::call_context(
-                    &mut stack,
-                    memory,
-                    chain_state,
-                    ContextType::Static,
-                    is_static,
-                );
-            }
-            opcodes::RETURNDATASIZE => {
-                let return_data_size = last_context_ret
-                    .as_ref()
-                    .map(|r| U256::from(r.len()))
-                    .unwrap_or_default();
+    let Some(&opcode) = code.get(*pc) else {
+        return Some(Interrupt::Halt(Outcome::stop()));
+    };
+    *pc += 1;
 
-                stack.push(return_data_size);
-            }
-            opcodes::RETURNDATACOPY => {
-                let dest_offset = stack.pop().unwrap().as_usize();
-                let offset = stack.pop().unwrap().as_usize();
-                let size = stack.pop().unwrap().as_usize();
+    if !spec.enabled(opcodes::introduced_in(opcode)) {
+        return Some(Interrupt::Halt(Outcome::exceptional(
+            ExecutionError::InvalidOpcode(opcode),
+        )));
+    }
 
-                let mut buf = vec![0u8; size];
-                last_context_ret.as_ref().unwrap()[offset..].clone_into(&mut buf);
+    if let Err(err) = gas.charge(gas::static_cost(opcode, spec)) {
+        return Some(Interrupt::Halt(Outcome::exceptional(err)));
+    }
 
-                write_memory(memory, dest_offset, buf);
+    let mut child = None;
+    let res = match opcode {
+        opcodes::STOP => return Some(Interrupt::Halt(Outcome::stop())),
+        opcodes::PUSH0..=opcodes::PUSH32 => {
+            let n_bytes = (opcode - opcodes::PUSH0) as usize;
+            *pc += n_bytes;
+            operations::push(*pc - n_bytes, n_bytes, code, stack)
+        }
+        opcodes::POP => stack.pop().map(drop),
+        opcodes::ADD => operations::add(stack),
+        opcodes::MUL => operations::mul(stack),
+        opcodes::SUB => operations::sub(stack),
+        opcodes::DIV => operations::div(stack),
+        opcodes::SDIV => operations::signed_div(stack),
+        opcodes::MOD => operations::modulo(stack),
+        opcodes::SMOD => operations::signed_modulo(stack),
+        opcodes::ADDMOD => operations::addmod(stack),
+        opcodes::MULMOD => operations::mulmod(stack),
+        opcodes::EXP => operations::exp(stack, gas, spec),
+        opcodes::SIGNEXTEND => operations::signextend(stack),
+        opcodes::LT => operations::less_than(stack),
+        opcodes::GT => operations::greater_than(stack),
+        opcodes::SLT => operations::signed_less_than(stack),
+        opcodes::SGT => operations::signed_greater_than(stack),
+        opcodes::EQ => operations::equal(stack),
+        opcodes::ISZERO => operations::is_zero(stack),
+        opcodes::AND => operations::and(stack),
+        opcodes::OR => operations::or(stack),
+        opcodes::XOR => operations::xor(stack),
+        opcodes::NOT => operations::not(stack),
+        opcodes::KECCAK256 => operations::keccak256(stack, memory, gas),
+        opcodes::BYTE => operations::byte(stack),
+        opcodes::SHL => operations::shl(stack),
+        opcodes::SHR => operations::shr(stack),
+        opcodes::SAR => operations::sar(stack),
+        opcodes::DUP1..=opcodes::DUP16 => {
+            let offset = opcode - opcodes::DUP1 + 1;
+            operations::duplicate(offset as usize, stack)
+        }
+        opcodes::SWAP1..=opcodes::SWAP16 => {
+            let offset = opcode - opcodes::SWAP1 + 1;
+            operations::swap(offset as usize, stack)
+        }
+        opcodes::PC => stack.push((*pc - 1).into()),
+        opcodes::GAS => stack.push(gas.remaining().into()),
+        opcodes::JUMP => operations::jump(stack, jump_destinations, pc),
+        opcodes::JUMPI => operations::jump_if(stack, jump_destinations, pc),
+        opcodes::MSIZE => operations::memsize(stack, memory),
+        opcodes::MSTORE => operations::memstore(stack, memory, gas),
+        opcodes::MSTORE8 => operations::memstore8(stack, memory, gas),
+        opcodes::MLOAD => operations::memload(stack, memory, gas),
+        opcodes::MCOPY => operations::memcopy(stack, memory, gas),
+        opcodes::ADDRESS => stack.push(message.address.into()),
+        opcodes::CALLER => stack.push(message.caller.into()),
+        opcodes::ORIGIN => stack.push(chain_state.tx.origin.unwrap_or_default().into()),
+        opcodes::GASPRICE => stack.push(chain_state.tx.gasprice.unwrap_or_default().into()),
+        opcodes::BASEFEE => stack.push(chain_state.block.basefee.unwrap_or_default().into()),
+        opcodes::BLOBBASEFEE => stack.push(chain_state.block.blob_base_fee(spec)),
+        opcodes::BLOBHASH => operations::blob_hash(stack, &chain_state.tx),
+        opcodes::COINBASE => stack.push(chain_state.block.coinbase.unwrap_or_default().into()),
+        opcodes::TIMESTAMP => stack.push(chain_state.block.timestamp.unwrap_or_default().into()),
+        opcodes::NUMBER => stack.push(chain_state.block.number.unwrap_or_default().into()),
+        // before the merge, the same opcode was DIFFICULTY
+        opcodes::PREVRANDAO if !spec.enabled(SpecId::Merge) => {
+            stack.push(chain_state.block.difficulty.unwrap_or_default().into())
+        }
+        opcodes::PREVRANDAO => stack.push(chain_state.block.prevrandao().into()),
+        opcodes::GASLIMIT => stack.push(chain_state.block.gaslimit.unwrap_or_default().into()),
+        opcodes::CHAINID => stack.push(chain_state.block.chainid.unwrap_or_default().into()),
+        opcodes::CALLVALUE => operations::call_value(stack, message),
+        opcodes::CALLDATALOAD => operations::call_data_load(stack, message),
+        opcodes::CALLDATASIZE => operations::call_data_size(stack, message),
+        opcodes::CALLDATACOPY => operations::call_data_copy(stack, memory, message, gas),
+        opcodes::CODESIZE => stack.push(code.len().into()),
+        opcodes::CODECOPY => operations::code_copy(stack, memory, code, gas),
+        opcodes::EXTCODESIZE => operations::external_code_size(stack, chain_state, gas, spec),
+        opcodes::EXTCODECOPY => {
+            operations::external_code_copy(stack, memory, chain_state, gas, spec)
+        }
+        opcodes::EXTCODEHASH => operations::external_code_hash(stack, chain_state, gas, spec),
+        opcodes::BALANCE => operations::get_balance(stack, chain_state, gas, spec),
+        opcodes::SELFBALANCE => operations::self_balance(stack, chain_state, message),
+        opcodes::SLOAD => operations::storage_load(stack, chain_state, message, gas, spec),
+        opcodes::SSTORE if is_static => Err(ExecutionError::WriteProtection),
+        opcodes::SSTORE => operations::storage_store(stack, chain_state, message, gas, spec),
+        opcodes::TLOAD => operations::transient_load(stack, chain_state, message),
+        opcodes::TSTORE if is_static => Err(ExecutionError::WriteProtection),
+        opcodes::TSTORE => operations::transient_store(stack, chain_state, message),
+        opcodes::LOG0..=opcodes::LOG4 if is_static => Err(ExecutionError::WriteProtection),
+        opcodes::LOG0..=opcodes::LOG4 => {
+            let n_topics = opcode - opcodes::LOG0;
+            operations::log(n_topics, stack, memory, message, gas).map(|log| logs.push(log))
+        }
+        opcodes::RETURN => match operations::return_value(stack, memory, gas) {
+            Ok(return_value) => {
+                return Some(Interrupt::Halt(Outcome {
+                    success: true,
+                    error: None,
+                    ret: Some(return_value),
+                }))
             }
-            opcodes::CREATE => {
-                if is_static {
-                    let return_value = operations::revert_context(&mut stack, memory);
-                    ret = Some(return_value);
-                    success = false;
-                    break;
-                }
-
-                operations::create_contract(&mut stack, memory, storage, chain_state)
+            Err(err) => Err(err),
+        },
+        opcodes::REVERT => match operations::revert_context(stack, memory, gas) {
+            Ok(return_value) => {
+                return Some(Interrupt::Halt(Outcome {
+                    success: false,
+                    error: None,
+                    ret: Some(return_value),
+                }))
             }
-            opcodes::SELFDESTRUCT => {
-                if is_static {
-                    let return_value = operations::revert_context(&mut stack, memory);
-                    ret = Some(return_value);
-                    success = false;
-                    break;
-                }
+            Err(err) => Err(err),
+        },
+        opcodes::CALL => operations::call_context(
+            stack,
+            memory,
+            chain_state,
+            message,
+            ContextType::Writeable,
+            gas,
+            spec,
+        )
+        .map(|sub_frame| {
+            *last_context_ret = None;
+            child = sub_frame;
+        }),
+        opcodes::CALLCODE => operations::call_context(
+            stack,
+            memory,
+            chain_state,
+            message,
+            ContextType::CallCode,
+            gas,
+            spec,
+        )
+        .map(|sub_frame| {
+            *last_context_ret = None;
+            child = sub_frame;
+        }),
+        opcodes::DELEGATECALL => operations::call_context(
+            stack,
+            memory,
+            chain_state,
+            message,
+            ContextType::WriteableDelegate,
+            gas,
+            spec,
+        )
+        .map(|sub_frame| {
+            *last_context_ret = None;
+            child = sub_frame;
+        }),
+        opcodes::STATICCALL => operations::call_context(
+            stack,
+            memory,
+            chain_state,
+            message,
+            ContextType::Static,
+            gas,
+            spec,
+        )
+        .map(|sub_frame| {
+            *last_context_ret = None;
+            child = sub_frame;
+        }),
+        opcodes::RETURNDATASIZE => {
+            let return_data_size = last_context_ret
+                .as_ref()
+                .map(|r| U256::from(r.len()))
+                .unwrap_or_default();
 
-                operations::self_destruct(&mut stack, chain_state)
-            }
-            opcodes::JUMPDEST => continue,
-            opcodes::BLOCKHASH => continue, // NOTE: not implemented in the test suite
-            _ => {
-                success = false;
-                break;
+            stack.push(return_data_size)
+        }
+        opcodes::RETURNDATACOPY => {
+            operations::return_data_copy(stack, memory, last_context_ret.as_ref(), gas)
+        }
+        opcodes::CREATE | opcodes::CREATE2 if is_static => Err(ExecutionError::WriteProtection),
+        opcodes::CREATE => {
+            operations::create_contract(stack, memory, chain_state, message, gas, spec).map(
+                |sub_frame| {
+                    *last_context_ret = None;
+                    child = sub_frame;
+                },
+            )
+        }
+        opcodes::CREATE2 => {
+            operations::create2_contract(stack, memory, chain_state, message, gas, spec).map(
+                |sub_frame| {
+                    *last_context_ret = None;
+                    child = sub_frame;
+                },
+            )
+        }
+        opcodes::SELFDESTRUCT if is_static => Err(ExecutionError::WriteProtection),
+        opcodes::SELFDESTRUCT => {
+            match operations::self_destruct(stack, chain_state, message, gas, spec) {
+                Ok(()) => return Some(Interrupt::Halt(Outcome::stop())),
+                Err(err) => Err(err),
             }
         }
+        opcodes::JUMPDEST => Ok(()),
+        opcodes::BLOCKHASH => operations::block_hash(stack, &chain_state.block),
+        _ => Err(ExecutionError::InvalidOpcode(opcode)),
+    };
+
+    if let Err(err) = res {
+        return Some(Interrupt::Halt(Outcome::exceptional(err)));
     }
 
-    stack.reverse();
+    child.map(|sub_frame| Interrupt::Enter(Box::new(sub_frame)))
+}
 
-    EvmResult {
-        stack,
-        success,
-        logs,
-        ret,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state::TxData;
+
+    #[test]
+    fn pauses_between_instructions() {
+        // PUSH1 1, PUSH1 2, ADD
+        let code = hex::decode("6001600201").unwrap();
+        let tx = TxData::default();
+        let message = Message::transaction(&tx, 1_000_000);
+        let mut chain_state = BlockchainState::new(tx, Default::default(), Default::default());
+        let mut memory = Vec::new();
+
+        let evm = Evm::new();
+        let mut execution = evm.start(&code, &mut memory, &mut chain_state, &message);
+        assert_eq!(execution.pc(), Some(0));
+
+        assert!(!execution.step());
+        assert!(!execution.step());
+        assert_eq!(execution.pc(), Some(4));
+        assert_eq!(execution.depth(), Some(0));
+        assert_eq!(execution.stack(), Some(vec![2.into(), 1.into()]));
+
+        let result = execution.finish();
+        assert!(result.success);
+        assert_eq!(result.stack, vec![U256::from(3)]);
+    }
+
+    #[test]
+    fn steps_into_a_sub_call() {
+        // CALL the recipient itself without any gas, so the sub-frame runs out of it on its
+        // first instruction
+        let code = hex::decode("60006000600060006000306000f1").unwrap();
+        let tx = TxData::default();
+        let message = Message::transaction(&tx, 1_000_000);
+        let mut chain_state = BlockchainState::new(tx, Default::default(), Default::default());
+        chain_state.set_code(message.address, code.clone());
+        let mut memory = Vec::new();
+
+        let evm = Evm::new();
+        let mut execution = evm.start(&code, &mut memory, &mut chain_state, &message);
+        for _ in 0..8 {
+            assert!(!execution.step());
+        }
+        assert_eq!(execution.depth(), Some(1));
+        assert_eq!(execution.pc(), Some(0));
+
+        let result = execution.finish();
+        assert!(result.success);
+        assert_eq!(result.stack, vec![U256::zero()]);
     }
 }
@@ -1,4 +1,4 @@
-/**
+/*
  * EVM From Scratch
  * Rust template
  *
@@ -15,12 +15,15 @@
 
 use std::mem;
 use colored::Colorize;
-use evm::evm;
+use evm::message::Message;
+use evm::spec::SpecId;
 use evm::state;
+use evm::Evm;
 use evm::EvmLog;
 use primitive_types::U256;
 use serde::Deserialize;
-use std::collections::HashMap;
+
+const GAS_LIMIT: u64 = 30_000_000;
 
 #[derive(Debug, Deserialize)]
 struct Evmtest {
@@ -31,6 +34,8 @@ struct Evmtest {
     tx: Option<state::TxData>,
     block: Option<state::BlockData>,
     state: Option<state::ContractsStateData>,
+    #[serde(default)]
+    spec: SpecId,
 }
 
 #[derive(Debug, Deserialize)]
@@ -61,16 +66,16 @@ fn main() {
         println!("Test {} of {}: {}", index + 1, total, test.name);
 
         let code: Vec<u8> = hex::decode(&test.code.bin).unwrap();
-        let mut chain_state = state::BlockchainState {
-            tx: mem::take(&mut test.tx).unwrap_or_default(),
-            block: mem::take(&mut test.block).unwrap_or_default(),
-            contracts_state: mem::take(&mut test.state).unwrap_or_default(),
-        };
+        let mut chain_state = state::BlockchainState::new(
+            mem::take(&mut test.tx).unwrap_or_default(),
+            mem::take(&mut test.block).unwrap_or_default(),
+            mem::take(&mut test.state).unwrap_or_default(),
+        );
 
         let mut memory = Vec::<u8>::new();
-        let mut storage = HashMap::<U256, U256>::new();
+        let message = Message::transaction(&chain_state.tx, GAS_LIMIT);
 
-        let result = evm(&code, &mut memory, &mut storage, &mut chain_state, false);
+        let result = Evm::with_spec(test.spec).run(&code, &mut memory, &mut chain_state, &message);
 
         let mut expected_stack: Vec<U256> = Vec::new();
         if let Some(ref stacks) = test.expect.stack {
@@ -79,15 +84,7 @@ fn main() {
             }
         }
 
-        let mut matching = result.stack.len() == expected_stack.len();
-        if matching {
-            for i in 0..result.stack.len() {
-                if result.stack[i] != expected_stack[i] {
-                    matching = false;
-                    break;
-                }
-            }
-        }
+        let mut matching = result.stack == expected_stack;
 
         let logs_ok =
             test.expect.logs.is_none() || test.expect.logs.as_ref().unwrap() == &result.logs;
@@ -116,6 +113,7 @@ fn main() {
             }
 
             println!("Actual success: {:?}", result.success);
+            println!("Actual error: {:?}", result.error);
             println!("Actual return: {:?}", result.ret);
             println!("Actual stack: [");
             for v in result.stack {
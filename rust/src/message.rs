@@ -0,0 +1,39 @@
+use primitive_types::U256;
+
+use crate::state::{SerializedU256, TxData};
+
+/// Maximum number of frames nested below the transaction's own.
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+
+/// How a call frame was entered: who called it, on behalf of which account and with what.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub caller: SerializedU256,
+    /// Account whose storage and balance the frame works on.
+    pub address: SerializedU256,
+    /// Account the running code was loaded from, which differs from `address` in a DELEGATECALL.
+    pub code_address: SerializedU256,
+    pub value: U256,
+    pub input: Vec<u8>,
+    pub gas: u64,
+    pub is_static: bool,
+    pub depth: usize,
+}
+
+impl Message {
+    /// Message of the outermost frame, as sent by the transaction itself.
+    pub fn transaction(tx: &TxData, gas: u64) -> Self {
+        let address = tx.to.unwrap_or_default();
+
+        Message {
+            caller: tx.from.unwrap_or_default(),
+            address,
+            code_address: address,
+            value: tx.value.unwrap_or_default().into(),
+            input: tx.data.clone().unwrap_or_default().into(),
+            gas,
+            is_static: false,
+            depth: 0,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::spec::SpecId;
+
 pub const STOP: u8 = 0x00;
 pub const ADD: u8 = 0x01;
 pub const MUL: u8 = 0x02;
@@ -43,11 +45,13 @@ pub const BLOCKHASH: u8 = 0x40;
 pub const COINBASE: u8 = 0x41;
 pub const TIMESTAMP: u8 = 0x42;
 pub const NUMBER: u8 = 0x43;
-pub const DIFFICULTY: u8 = 0x44;
+pub const PREVRANDAO: u8 = 0x44;
 pub const GASLIMIT: u8 = 0x45;
 pub const CHAINID: u8 = 0x46;
 pub const SELFBALANCE: u8 = 0x47;
 pub const BASEFEE: u8 = 0x48;
+pub const BLOBHASH: u8 = 0x49;
+pub const BLOBBASEFEE: u8 = 0x4a;
 pub const POP: u8 = 0x50;
 pub const MLOAD: u8 = 0x51;
 pub const MSTORE: u8 = 0x52;
@@ -60,7 +64,11 @@ pub const PC: u8 = 0x58;
 pub const MSIZE: u8 = 0x59;
 pub const GAS: u8 = 0x5a;
 pub const JUMPDEST: u8 = 0x5b;
+pub const TLOAD: u8 = 0x5c;
+pub const TSTORE: u8 = 0x5d;
+pub const MCOPY: u8 = 0x5e;
 pub const PUSH0: u8 = 0x5f;
+pub const PUSH1: u8 = 0x60;
 pub const PUSH32: u8 = 0x7f;
 pub const DUP1: u8 = 0x80;
 pub const DUP16: u8 = 0x8f;
@@ -71,10 +79,25 @@ pub const LOG4: u8 = 0xa4;
 pub const RETURN: u8 = 0xf3;
 pub const REVERT: u8 = 0xfd;
 pub const CREATE: u8 = 0xf0;
+pub const CREATE2: u8 = 0xf5;
 pub const CALL: u8 = 0xf1;
+pub const CALLCODE: u8 = 0xf2;
 pub const DELEGATECALL: u8 = 0xf4;
 pub const STATICCALL: u8 = 0xfa;
 pub const RETURNDATASIZE: u8 = 0x3d;
 pub const RETURNDATACOPY: u8 = 0x3e;
 pub const SELFDESTRUCT: u8 = 0xff;
 
+/// Hardfork that made `opcode` available, Frontier for those that always were.
+pub fn introduced_in(opcode: u8) -> SpecId {
+    match opcode {
+        DELEGATECALL => SpecId::Homestead,
+        REVERT | RETURNDATASIZE | RETURNDATACOPY | STATICCALL => SpecId::Byzantium,
+        SHL | SHR | SAR | CREATE2 | EXTCODEHASH => SpecId::Constantinople,
+        CHAINID | SELFBALANCE => SpecId::Istanbul,
+        BASEFEE => SpecId::London,
+        PUSH0 => SpecId::Shanghai,
+        TLOAD | TSTORE | MCOPY | BLOBHASH | BLOBBASEFEE => SpecId::Cancun,
+        _ => SpecId::Frontier,
+    }
+}
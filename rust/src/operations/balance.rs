@@ -1,27 +1,29 @@
-use primitive_types::U256;
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    message::Message,
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockchainState, SerializedU256},
+};
 
-use crate::state::{BlockchainState, SerializedU256};
+pub fn get_balance(
+    stack: &mut Stack,
+    state: &mut BlockchainState,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let address: SerializedU256 = stack.pop()?.into();
+    let is_cold = state.warm_address(address);
+    gas.charge(gas::account_access_cost(is_cold, spec))?;
 
-pub fn get_balance(stack: &mut Vec<U256>, state: &BlockchainState) {
-    let address: SerializedU256 = stack.pop().unwrap().into();
-    let entry = state
-        .contracts_state
-        .get(&address)
-        .map(|entry| entry.balance)
-        .flatten()
-        .unwrap_or_default();
-
-    stack.push(entry.into());
+    stack.push(state.balance(address))
 }
 
-pub fn self_balance(stack: &mut Vec<U256>, state: &BlockchainState) {
-    let address: SerializedU256 = state.tx.to.unwrap();
-    let entry = state
-        .contracts_state
-        .get(&address)
-        .map(|entry| entry.balance)
-        .flatten()
-        .unwrap_or_default();
-
-    stack.push(entry.into());
+pub fn self_balance(
+    stack: &mut Stack,
+    state: &BlockchainState,
+    message: &Message,
+) -> Result<(), ExecutionError> {
+    stack.push(state.balance(message.address))
 }
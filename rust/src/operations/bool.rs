@@ -1,80 +1,89 @@
 use primitive_types::U256;
 
+use crate::error::ExecutionError;
 use crate::helpers::AsI256;
+use crate::stack::Stack;
 
-pub fn not(stack: &mut Vec<U256>) {
-    let n = stack.pop().unwrap();
+pub fn not(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n = stack.pop()?;
 
-    stack.push(!n);
+    stack.push(!n)
 }
 
-pub fn and(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn and(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
 
-    stack.push(n1 & n2);
+    stack.push(n1 & n2)
 }
 
-pub fn or(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn or(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
 
-    stack.push(n1 | n2);
+    stack.push(n1 | n2)
 }
 
-pub fn xor(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn xor(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
 
-    stack.push(n1 ^ n2);
+    stack.push(n1 ^ n2)
 }
 
-pub fn byte(stack: &mut Vec<U256>) {
-    let index = stack.pop().unwrap().as_usize();
-    let n = stack.pop().unwrap().as_i256();
+pub fn byte(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let index = stack.pop()?;
+    let n = stack.pop()?;
     let mut res = U256::zero();
-    if index <= 32 {
-        res = n.to_be_bytes()[index].into();
+    if index < 32.into() {
+        res = n.byte(31 - index.as_usize()).into();
     }
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn shl(stack: &mut Vec<U256>) {
-    let shift = stack.pop().unwrap().as_usize();
-    let n = stack.pop().unwrap();
-    let res = if shift > 255 {
+pub fn shl(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let shift = stack.pop()?;
+    let n = stack.pop()?;
+    let res = if shift > 255.into() {
         U256::zero()
     } else {
-        n << shift
+        n << shift.as_usize()
     };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn shr(stack: &mut Vec<U256>) {
-    let shift = stack.pop().unwrap().as_usize();
-    let n = stack.pop().unwrap();
-    let res = if shift > 255 {
+pub fn shr(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let shift = stack.pop()?;
+    let n = stack.pop()?;
+    let res = if shift > 255.into() {
         U256::zero()
     } else {
-        n >> shift
+        n >> shift.as_usize()
     };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn sar(stack: &mut Vec<U256>) {
-    let shift = stack.pop().unwrap().as_usize();
-    let n = stack.pop().unwrap();
-    let mut res = n >> shift;
-    if n.as_i256().is_negative() {
-        if shift > 255 {
-            res = U256::MAX;
+pub fn sar(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let shift = stack.pop()?;
+    let n = stack.pop()?;
+    let is_negative = n.as_i256().is_negative();
+    let res = if shift > 255.into() {
+        if is_negative {
+            U256::MAX
         } else {
-            res += U256::MAX << (256 - shift);
+            U256::zero()
         }
-    }
+    } else {
+        let shift = shift.as_usize();
+        let mut res = n >> shift;
+        if is_negative && shift > 0 {
+            res |= U256::MAX << (256 - shift);
+        }
+        res
+    };
 
-    stack.push(res);
+    stack.push(res)
 }
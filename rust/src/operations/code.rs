@@ -1,66 +1,81 @@
-use std::cmp::min;
-
-use primitive_types::U256;
-
-use crate::state::{BlockchainState, ContractsStateDataEntry};
-
-use super::write_memory;
-
-pub fn code_copy(stack: &mut Vec<U256>, memory: &mut Vec<u8>, code: &Vec<u8>) {
-    let memory_dest_offset = stack.pop().unwrap().as_usize();
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
-
-    let mut buf = vec![0u8; size];
-    let end = min(offset + size, code.len());
-    code[offset..end].clone_into(&mut buf);
-
-    let buf_len = buf.len();
-    if buf_len < 32 {
-        buf.append(&mut vec![0u8; 64 - buf_len])
-    }
-
-    write_memory(memory, memory_dest_offset, buf)
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockchainState, ContractsStateDataEntry},
+};
+
+use super::{expand_memory, read_padded, write_memory};
+
+pub fn code_copy(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    code: &[u8],
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let memory_dest_offset = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let (memory_dest_offset, size) = expand_memory(memory, memory_dest_offset, size, gas)?;
+    gas.charge(gas::COPY * gas::words(size))?;
+
+    let buf = read_padded(code, offset, size);
+
+    write_memory(memory, memory_dest_offset, &buf);
+    Ok(())
 }
 
-pub fn external_code_size(stack: &mut Vec<U256>, state: &BlockchainState) {
-    let address = stack.pop().unwrap();
+pub fn external_code_size(
+    stack: &mut Stack,
+    state: &mut BlockchainState,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let address = stack.pop()?;
+    let is_cold = state.warm_address(address.into());
+    gas.charge(gas::account_access_cost(is_cold, spec))?;
 
     let code = state.contracts_state.get(&(address.into()));
     let len = code
-        .map(|entry| entry.code.clone().unwrap().len())
+        .and_then(|entry| entry.code.as_ref())
+        .map(|code| code.len())
         .unwrap_or_default();
 
     stack.push(len.into())
 }
 
-pub fn external_code_copy(stack: &mut Vec<U256>, memory: &mut Vec<u8>, state: &BlockchainState) {
-    let address = stack.pop().unwrap();
-    let memory_dest_offset = stack.pop().unwrap().as_usize();
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
+pub fn external_code_copy(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    state: &mut BlockchainState,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let address = stack.pop()?;
+    let memory_dest_offset = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let is_cold = state.warm_address(address.into());
+    gas.charge(gas::account_access_cost(is_cold, spec))?;
+
+    let (memory_dest_offset, size) = expand_memory(memory, memory_dest_offset, size, gas)?;
+    gas.charge(gas::COPY * gas::words(size))?;
 
     let code = state
         .contracts_state
         .get(&(address.into()))
         .map(ContractsStateDataEntry::to_owned)
-        .map(|entry| entry.code)
-        .flatten();
-
-    if code.is_none() {
-        stack.push(0.into());
-        return;
-    }
-
-    let bin: Vec<u8> = code.unwrap().bin.unwrap_or_default().into();
-    let mut buf = vec![0u8; size];
-    let end = min(offset + size, bin.len());
-    bin[offset..end].clone_into(&mut buf);
+        .and_then(|entry| entry.code);
 
-    let buf_len = buf.len();
-    if buf_len < 32 {
-        buf.append(&mut vec![0u8; 64 - buf_len])
-    }
+    let bin: Vec<u8> = code
+        .and_then(|code| code.bin)
+        .unwrap_or_default()
+        .into();
+    let buf = read_padded(&bin, offset, size);
 
-    write_memory(memory, memory_dest_offset, buf)
+    write_memory(memory, memory_dest_offset, &buf);
+    Ok(())
 }
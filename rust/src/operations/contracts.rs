@@ -1,80 +1,266 @@
-use std::collections::HashMap;
-
 use primitive_types::U256;
 
 use crate::{
-    evm,
-    state::{
-        BlockchainState, ContractsStateDataEntry, ContractsStateDataEntryCode, SerializedBytes,
-        SerializedU256,
-    },
+    error::ExecutionError,
+    frame::{Frame, FrameKind},
+    gas::{self, Gas},
+    message::{Message, CALL_DEPTH_LIMIT},
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockchainState, SerializedU256},
 };
 
-use super::read_memory_bytes;
+use super::{expand_memory, hash, read_memory_bytes};
+
+/// Largest code a contract can be deployed with (EIP-170).
+pub const MAX_CODE_SIZE: usize = 24576;
+/// Largest init code CREATE and CREATE2 accept (EIP-3860).
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// RLP encoding of an integer: its big-endian bytes with the leading zeros stripped.
+fn rlp_encode_integer(value: U256, out: &mut Vec<u8>) {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let first_significant = bytes.iter().position(|b| *b != 0).unwrap_or(32);
+    let bytes = &bytes[first_significant..];
+
+    match bytes {
+        [byte] if *byte < 0x80 => out.push(*byte),
+        _ => {
+            out.push(0x80 + bytes.len() as u8);
+            out.extend_from_slice(bytes);
+        }
+    }
+}
+
+/// Address of a contract created with CREATE: keccak256(rlp([sender, nonce]))[12..].
+fn create_address(sender: U256, nonce: U256) -> U256 {
+    let mut sender_bytes = [0u8; 32];
+    sender.to_big_endian(&mut sender_bytes);
+
+    let mut payload = vec![0x80 + 20];
+    payload.extend_from_slice(&sender_bytes[12..]);
+    rlp_encode_integer(nonce, &mut payload);
+
+    // the payload never reaches 56 bytes, so the list prefix is a single byte
+    let mut encoded = vec![0xc0 + payload.len() as u8];
+    encoded.extend(payload);
+
+    let mut address = [0u8; 32];
+    hash(&encoded).to_big_endian(&mut address);
+    address[..12].fill(0);
+
+    U256::from_big_endian(&address)
+}
+
+/// Address of a contract created with CREATE2:
+/// keccak256(0xff ++ sender ++ salt ++ keccak256(init_code))[12..].
+fn create2_address(sender: U256, salt: U256, init_code: &[u8]) -> U256 {
+    let mut preimage = [0u8; 85];
+    preimage[0] = 0xff;
+
+    let mut sender_bytes = [0u8; 32];
+    sender.to_big_endian(&mut sender_bytes);
+    preimage[1..21].copy_from_slice(&sender_bytes[12..]);
+    salt.to_big_endian(&mut preimage[21..53]);
+    hash(init_code).to_big_endian(&mut preimage[53..]);
+
+    let mut address = [0u8; 32];
+    hash(&preimage).to_big_endian(&mut address);
+    address[..12].fill(0);
+
+    U256::from_big_endian(&address)
+}
 
 pub fn create_contract(
-    stack: &mut Vec<U256>,
+    stack: &mut Stack,
     memory: &mut Vec<u8>,
-    storage: &mut HashMap<U256, U256>,
     chain_state: &mut BlockchainState,
-) {
-    let balance = stack.pop().unwrap();
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<Option<Frame>, ExecutionError> {
+    let value = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
 
-    let code = if size > 0 {
-        read_memory_bytes(memory, offset, size)
-    } else {
-        vec![]
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
+    charge_init_code(size, gas, spec)?;
+    let code = read_memory_bytes(memory, offset, size);
+
+    let creator = message.address;
+    let address = create_address(creator.into(), chain_state.nonce(creator));
+
+    let contract = NewContract {
+        address,
+        code,
+        value,
+    };
+    deploy(stack, chain_state, message, gas, spec, contract)
+}
+
+pub fn create2_contract(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<Option<Frame>, ExecutionError> {
+    let value = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+    let salt = stack.pop()?;
+
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
+    charge_init_code(size, gas, spec)?;
+    gas.charge(gas::KECCAK256_WORD * gas::words(size))?;
+    let code = read_memory_bytes(memory, offset, size);
+
+    let creator = message.address;
+    let address = create2_address(creator.into(), salt, &code);
+
+    let contract = NewContract {
+        address,
+        code,
+        value,
     };
-    let address = U256::from(1234); // TODO:
-
-    let res = evm(&code, memory, storage, chain_state, false);
-    let contract_code = res.ret.unwrap_or_default();
-    let has_reverted = !res.success;
-
-    if !has_reverted {
-        chain_state.contracts_state.insert(
-            SerializedU256 { value: address },
-            ContractsStateDataEntry {
-                code: Some(ContractsStateDataEntryCode {
-                    bin: Some(SerializedBytes {
-                        value: contract_code,
-                    }),
-                }),
-                balance: Some(SerializedU256 { value: balance }),
-            },
-        );
+    deploy(stack, chain_state, message, gas, spec, contract)
+}
+
+/// Since Shanghai (EIP-3860), init code is capped in size and costs 2 gas per word.
+fn charge_init_code(size: usize, gas: &mut Gas, spec: SpecId) -> Result<(), ExecutionError> {
+    if !spec.enabled(SpecId::Shanghai) {
+        return Ok(());
     }
 
-    stack.push(if has_reverted { U256::zero() } else { address });
+    if size > MAX_INITCODE_SIZE {
+        return Err(ExecutionError::InitCodeSizeExceeded);
+    }
+    gas.charge(gas::INITCODE_WORD * gas::words(size))
 }
 
-pub fn self_destruct(stack: &mut Vec<U256>, chain_state: &mut BlockchainState) {
-    let address = stack.pop().unwrap();
-    let current_address = chain_state.tx.to.unwrap();
+/// Account that a CREATE or CREATE2 is about to deploy.
+struct NewContract {
+    address: U256,
+    /// Init code, whose output becomes the code of the account.
+    code: Vec<u8>,
+    /// Wei the account is endowed with.
+    value: U256,
+}
+
+/// Sets up a frame running the init code of `contract`, or pushes zero if the account cannot
+/// be created.
+fn deploy(
+    stack: &mut Stack,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+    contract: NewContract,
+) -> Result<Option<Frame>, ExecutionError> {
+    let NewContract {
+        address,
+        code,
+        value,
+    } = contract;
+
+    // before EIP-150, the init code got all the gas that was left
+    let create_gas = if spec.enabled(SpecId::TangerineWhistle) {
+        gas.forward(U256::MAX)
+    } else {
+        gas.forward_exact(gas.remaining().into())?
+    };
+    let creator = message.address;
+    let creator_nonce = chain_state.nonce(creator);
+    if message.depth >= CALL_DEPTH_LIMIT
+        || chain_state.balance(creator) < value
+        || creator_nonce >= u64::MAX.into()
+    {
+        gas.reclaim(create_gas);
+        stack.push(U256::zero())?;
+        return Ok(None);
+    }
 
-    let balance = chain_state
-        .contracts_state
-        .remove(&current_address)
-        .unwrap()
-        .balance
-        .unwrap_or_default();
+    let address_key = SerializedU256 { value: address };
+    chain_state.set_nonce(creator, creator_nonce + 1);
+    chain_state.warm_address(address_key);
 
-    let destination_contract_key = SerializedU256 { value: address };
-    let mut destination_contract = chain_state
-        .contracts_state
-        .remove(&destination_contract_key)
-        .unwrap_or_default();
+    // an account that already has code or sent transactions can never be overwritten
+    if !chain_state.nonce(address_key).is_zero() || !chain_state.code(address_key).is_empty() {
+        stack.push(U256::zero())?;
+        return Ok(None);
+    }
 
-    destination_contract.balance = {
-        let b1: U256 = destination_contract.balance.unwrap_or_default().into();
-        let b2: U256 = balance.into();
+    let checkpoint = chain_state.checkpoint();
+    chain_state.mark_created(address_key);
 
-        Some(SerializedU256 { value: b1 + b2 })
+    // EIP-161 starts new contracts at nonce one, so that they are never empty
+    if spec.enabled(SpecId::SpuriousDragon) {
+        chain_state.set_nonce(address_key, U256::one());
+    }
+    chain_state.transfer(creator, address_key, value);
+
+    // the init code runs as the new account, so that its storage writes land there
+    let child_message = Message {
+        caller: creator,
+        address: address_key,
+        code_address: address_key,
+        value,
+        input: Vec::new(),
+        gas: create_gas,
+        is_static: false,
+        depth: message.depth + 1,
     };
 
-    chain_state
-        .contracts_state
-        .insert(destination_contract_key, destination_contract);
+    Ok(Some(Frame::new(
+        child_message,
+        code,
+        FrameKind::Create { address },
+        checkpoint,
+    )))
+}
+
+/// Sends the whole balance of the current account to the beneficiary. Since Cancun
+/// (EIP-6780) the account itself is only deleted if it was created in the same transaction.
+pub fn self_destruct(
+    stack: &mut Stack,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let beneficiary: SerializedU256 = stack.pop()?.into();
+    let current_address = message.address;
+
+    if chain_state.warm_address(beneficiary) && spec.enabled(SpecId::Berlin) {
+        gas.charge(gas::COLD_ACCOUNT_ACCESS)?;
+    }
+
+    let balance = chain_state.balance(current_address);
+    let creates_account = if spec.enabled(SpecId::SpuriousDragon) {
+        !balance.is_zero() && chain_state.is_empty_account(beneficiary)
+    } else {
+        spec.enabled(SpecId::TangerineWhistle) && !chain_state.account_exists(beneficiary)
+    };
+    if creates_account {
+        gas.charge(gas::CALL_NEW_ACCOUNT)?;
+    }
+
+    chain_state.transfer(current_address, beneficiary, balance);
+
+    let deletes_account =
+        !spec.enabled(SpecId::Cancun) || chain_state.created_contracts.contains(&current_address);
+    if deletes_account {
+        // whatever is left, including a balance sent to itself, is burnt with the account
+        chain_state.set_balance(current_address, U256::zero());
+
+        // EIP-3529 removed the refund for self-destructing
+        let first_time = chain_state.schedule_selfdestruct(current_address);
+        if first_time && !spec.enabled(SpecId::London) {
+            chain_state.add_refund(gas::SELFDESTRUCT_REFUND);
+        }
+    }
+
+    Ok(())
 }
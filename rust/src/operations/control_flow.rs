@@ -1,60 +1,56 @@
 use primitive_types::U256;
 
+use crate::error::ExecutionError;
 use crate::opcodes;
+use crate::stack::Stack;
+
+/// Marks every JUMPDEST that is an actual instruction rather than part of some PUSH data.
+pub fn jump_destinations(code: &[u8]) -> Vec<bool> {
+    let mut destinations = vec![false; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if opcode == opcodes::JUMPDEST {
+            destinations[pc] = true;
+        } else if (opcodes::PUSH1..=opcodes::PUSH32).contains(&opcode) {
+            pc += (opcode - opcodes::PUSH0) as usize;
+        }
 
-pub struct InvalidJumpError;
-
-pub fn jump(stack: &mut Vec<U256>, code: &Vec<u8>, pc: &mut usize) -> Result<(), InvalidJumpError> {
-    let offset = stack.pop().unwrap().as_usize();
-    if code[offset] != opcodes::JUMPDEST {
-        return Err(InvalidJumpError);
+        pc += 1;
     }
 
-    for i in 1..=32 {
-        let opcode = code[offset - i];
-        if opcode <= opcodes::PUSH0 || opcode > opcodes::PUSH32 {
-            continue;
-        }
-
-        let n_bytes = (opcode - opcodes::PUSH0) as usize;
-        if n_bytes >= i {
-            return Err(InvalidJumpError);
-        }
+    destinations
+}
 
-        break;
+fn destination(offset: U256, jump_destinations: &[bool]) -> Result<usize, ExecutionError> {
+    if offset >= jump_destinations.len().into() || !jump_destinations[offset.as_usize()] {
+        return Err(ExecutionError::InvalidJump);
     }
 
-    *pc = offset;
-    Ok(())
+    Ok(offset.as_usize())
 }
 
-pub fn jump_if(
-    stack: &mut Vec<U256>,
-    code: &Vec<u8>,
+pub fn jump(
+    stack: &mut Stack,
+    jump_destinations: &[bool],
     pc: &mut usize,
-) -> Result<(), InvalidJumpError> {
-    let offset = stack.pop().unwrap().as_usize();
-    let condition = stack.pop().unwrap();
-    if code[offset] != opcodes::JUMPDEST {
-        return Err(InvalidJumpError);
-    }
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
 
-    for i in 1..=32 {
-        let opcode = code[offset - i];
-        if opcode <= opcodes::PUSH0 || opcode > opcodes::PUSH32 {
-            continue;
-        }
-
-        let n_bytes = (opcode - opcodes::PUSH0) as usize;
-        if n_bytes >= i {
-            return Err(InvalidJumpError);
-        }
+    *pc = destination(offset, jump_destinations)?;
+    Ok(())
+}
 
-        break;
-    }
+pub fn jump_if(
+    stack: &mut Stack,
+    jump_destinations: &[bool],
+    pc: &mut usize,
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
+    let condition = stack.pop()?;
 
     if condition != U256::zero() {
-        *pc = offset;
+        *pc = destination(offset, jump_destinations)?;
     }
 
     Ok(())
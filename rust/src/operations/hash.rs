@@ -1,38 +1,95 @@
 use primitive_types::U256;
 use sha3::{Digest, Keccak256};
 
-use crate::state::BlockchainState;
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockData, BlockchainState, TxData},
+};
 
-pub fn keccak256(stack: &mut Vec<U256>, memory: &mut Vec<u8>) {
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
-
-    let value = &memory[offset..(offset + size)];
+use super::{expand_memory, read_memory_bytes};
 
+pub fn hash(bytes: &[u8]) -> U256 {
     let mut hasher = Keccak256::new();
-    hasher.update(&value);
+    hasher.update(bytes);
+
+    hasher.finalize()[..].into()
+}
+
+/// Versioned hash of the blob at the given index of the transaction, or zero past the end.
+pub fn blob_hash(stack: &mut Stack, tx: &TxData) -> Result<(), ExecutionError> {
+    let index = stack.pop()?;
+
+    let hash = if index < tx.blob_versioned_hashes.len().into() {
+        tx.blob_versioned_hashes[index.as_usize()].value
+    } else {
+        U256::zero()
+    };
+
+    stack.push(hash)
+}
+
+/// Number of most recent blocks whose hashes BLOCKHASH can see.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+pub fn block_hash(stack: &mut Stack, block: &BlockData) -> Result<(), ExecutionError> {
+    let number = stack.pop()?;
+    let current: U256 = block.number.unwrap_or_default().into();
 
-    let hashed: U256 = hasher.finalize()[..].into();
+    if number >= current || current - number > BLOCK_HASH_HISTORY.into() {
+        return stack.push(U256::zero());
+    }
+
+    // without a chain behind it, a block hashes to keccak256 of its number
+    let value = block
+        .hashes
+        .get(&number.into())
+        .map(|hash| hash.value)
+        .unwrap_or_else(|| {
+            let mut bytes = [0u8; 32];
+            number.to_big_endian(&mut bytes);
+            hash(&bytes)
+        });
 
-    stack.push(hashed);
+    stack.push(value)
 }
 
-pub fn external_code_hash(stack: &mut Vec<U256>, state: &BlockchainState) {
-    let address = stack.pop().unwrap();
+pub fn keccak256(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
+    gas.charge(gas::KECCAK256_WORD * gas::words(size))?;
+
+    let value = read_memory_bytes(memory, offset, size);
+
+    stack.push(hash(&value))
+}
+
+pub fn external_code_hash(
+    stack: &mut Stack,
+    state: &mut BlockchainState,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let address = stack.pop()?;
+    let is_cold = state.warm_address(address.into());
+    gas.charge(gas::account_access_cost(is_cold, spec))?;
 
     let code = state.contracts_state.get(&(address.into()));
     let code = code.map(|entry| entry.code.clone().unwrap_or_default());
 
     if code.is_none() {
-        stack.push(0.into());
-        return;
+        return stack.push(0.into());
     }
 
-    let bin: Vec<u8> = code.unwrap().bin.unwrap().into();
-    let mut hasher = Keccak256::new();
-    hasher.update(&bin);
-
-    let hashed: U256 = hasher.finalize()[..].into();
+    let bin: Vec<u8> = code.unwrap().bin.unwrap_or_default().into();
 
-    stack.push(hashed);
+    stack.push(hash(&bin))
 }
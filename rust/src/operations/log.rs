@@ -1,8 +1,12 @@
 use primitive_types::U256;
 
 use crate::{
-    operations::read_memory_bytes,
-    state::{BlockchainState, SerializedU256},
+    error::ExecutionError,
+    gas::{self, Gas},
+    message::Message,
+    operations::{expand_memory, read_memory_bytes},
+    stack::Stack,
+    state::SerializedU256,
     EvmLog,
 };
 
@@ -29,27 +33,29 @@ impl IntoHexString for SerializedU256 {
 
 pub fn log(
     n_topics: u8,
-    stack: &mut Vec<U256>,
+    stack: &mut Stack,
     memory: &mut Vec<u8>,
-    state: &BlockchainState,
-) -> EvmLog {
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
+    message: &Message,
+    gas: &mut Gas,
+) -> Result<EvmLog, ExecutionError> {
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
     let topics = (0..n_topics)
-        .map(|_| stack.pop().unwrap())
-        .map(U256::into_hex_string)
-        .collect::<Vec<_>>();
+        .map(|_| stack.pop().map(U256::into_hex_string))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
+    gas.charge(gas::LOG_DATA * size as u64)?;
 
     let data_bytes = read_memory_bytes(memory, offset, size);
     let data = hex::encode(data_bytes);
 
-    let tx_to = state.tx.to.unwrap();
-    let address = tx_to.into_hex_string();
+    let address = message.address.into_hex_string();
     dbg!(&address);
 
-    EvmLog {
+    Ok(EvmLog {
         address,
         topics,
         data,
-    }
+    })
 }
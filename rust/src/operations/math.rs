@@ -1,147 +1,175 @@
 use arrow_buffer::i256;
 use primitive_types::U256;
+use crate::error::ExecutionError;
+use crate::gas::{self, Gas};
 use crate::helpers::*;
+use crate::spec::SpecId;
+use crate::stack::Stack;
 
-pub fn add(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn add(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let (res, _) = n1.overflowing_add(n2);
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn mul(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn mul(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let (res, _) = n1.overflowing_mul(n2);
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn sub(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn sub(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let (res, _) = n1.overflowing_sub(n2);
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn div(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn div(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let res = if n2 == 0.into() { n2 } else { n1 / n2 };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn modulo(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn modulo(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let res = if n2 == 0.into() { n2 } else { n1 % n2 };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn addmod(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
-    let n3 = stack.pop().unwrap();
-    let (res, _) = (n1 % n3).overflowing_add(n2 % n3);
+pub fn addmod(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
+    let n3 = stack.pop()?;
+    let res = if n3.is_zero() {
+        n3
+    } else {
+        let (res, _) = (n1 % n3).overflowing_add(n2 % n3);
+        res
+    };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn mulmod(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
-    let n3 = stack.pop().unwrap();
-    let (res, _) = (n1 % n3).overflowing_mul(n2 % n3);
+pub fn mulmod(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
+    let n3 = stack.pop()?;
+    let res = if n3.is_zero() {
+        n3
+    } else {
+        let (res, _) = (n1 % n3).overflowing_mul(n2 % n3);
+        res
+    };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn exp(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn exp(stack: &mut Stack, gas: &mut Gas, spec: SpecId) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
+    let exponent_bytes = n2.bits().div_ceil(8) as u64;
+
+    // repriced by EIP-160
+    let exp_byte = if spec.enabled(SpecId::SpuriousDragon) {
+        gas::EXP_BYTE
+    } else {
+        gas::EXP_BYTE_FRONTIER
+    };
+    gas.charge(exp_byte * exponent_bytes)?;
+
     let (res, _) = n1.overflowing_pow(n2);
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn signextend(stack: &mut Vec<U256>) {
-    let b = stack.pop().unwrap().as_u32();
-    let x = stack.pop().unwrap();
-    let x_size_bits = 8 * (b + 1);
+pub fn signextend(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let b = stack.pop()?;
+    let x = stack.pop()?;
+    if b >= 31.into() {
+        return stack.push(x);
+    }
+
+    let x_size_bits = 8 * (b.as_u32() + 1);
 
-    let mut res = (x << x_size_bits) >> x_size_bits;
-    if x >> (x_size_bits - 1) == 1.into() {
+    let mut res = (x << (256 - x_size_bits)) >> (256 - x_size_bits);
+    if x.bit(x_size_bits as usize - 1) {
         res += U256::MAX << x_size_bits;
     }
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn signed_div(stack: &mut Vec<U256>) {
-    let n1: i256 = stack.pop().unwrap().as_i256();
-    let n2 = stack.pop().unwrap().as_i256();
-    let res = if n2 == 0.into() { n2 } else { n1 / n2 };
+pub fn signed_div(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1: i256 = stack.pop()?.as_i256();
+    let n2 = stack.pop()?.as_i256();
+    let res = if n2 == 0.into() { n2 } else { n1.wrapping_div(n2) };
 
-    stack.push(res.as_u256());
+    stack.push(res.as_u256())
 }
 
-pub fn signed_modulo(stack: &mut Vec<U256>) {
-    let n1: i256 = stack.pop().unwrap().as_i256();
-    let n2 = stack.pop().unwrap().as_i256();
-    let res = if n2 == 0.into() { n2 } else { n1 % n2 };
+pub fn signed_modulo(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1: i256 = stack.pop()?.as_i256();
+    let n2 = stack.pop()?.as_i256();
+    let res = if n2 == 0.into() { n2 } else { n1.wrapping_rem(n2) };
 
-    stack.push(res.as_u256());
+    stack.push(res.as_u256())
 }
 
-pub fn less_than(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn less_than(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let res = if n1 < n2 { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn greater_than(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn greater_than(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let res = if n1 > n2 { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn signed_less_than(stack: &mut Vec<U256>) {
-    let n1: i256 = stack.pop().unwrap().as_i256();
-    let n2 = stack.pop().unwrap().as_i256();
+pub fn signed_less_than(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1: i256 = stack.pop()?.as_i256();
+    let n2 = stack.pop()?.as_i256();
     let res = if n1 < n2 { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn signed_greater_than(stack: &mut Vec<U256>) {
-    let n1: i256 = stack.pop().unwrap().as_i256();
-    let n2 = stack.pop().unwrap().as_i256();
+pub fn signed_greater_than(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1: i256 = stack.pop()?.as_i256();
+    let n2 = stack.pop()?.as_i256();
 
     let res = if n1 > n2 { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn equal(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
-    let n2 = stack.pop().unwrap();
+pub fn equal(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
+    let n2 = stack.pop()?;
     let res = if n1 == n2 { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
-pub fn is_zero(stack: &mut Vec<U256>) {
-    let n1 = stack.pop().unwrap();
+pub fn is_zero(stack: &mut Stack) -> Result<(), ExecutionError> {
+    let n1 = stack.pop()?;
     let res = if n1 == U256::zero() { U256::one() } else { U256::zero() };
 
-    stack.push(res);
+    stack.push(res)
 }
 
@@ -1,88 +1,127 @@
-use primitive_types::U256;
-
-fn expand_memory(memory: &mut Vec<u8>, new_size: usize) {
-    let current_size = memory.len();
-    let padding = if new_size % 32 == 0 {
-        0
-    } else {
-        31 - (new_size % 32)
-    };
+use std::cmp::min;
 
-    memory.append(&mut vec![0u8; new_size + padding - current_size + 1]);
-}
+use primitive_types::U256;
 
-pub fn read_memory_bytes(memory: &mut Vec<u8>, offset: usize, size: usize) -> Vec<u8> {
-    let current_size = memory.len();
-    let new_size = offset + size - 1;
-    if new_size >= current_size {
-        expand_memory(memory, new_size);
+use crate::error::ExecutionError;
+use crate::gas::{self, Gas};
+use crate::stack::Stack;
+
+/// Grows `memory` so that `size` bytes at `offset` are addressable, charging the expansion cost.
+/// Touching zero bytes never expands memory, whatever the offset.
+pub fn expand_memory(
+    memory: &mut Vec<u8>,
+    offset: U256,
+    size: U256,
+    gas: &mut Gas,
+) -> Result<(usize, usize), ExecutionError> {
+    if size.is_zero() {
+        return Ok((0, 0));
     }
 
-    let mut bytes = Vec::<u8>::new();
-    for i in offset..=new_size {
-        bytes.push(memory[i].into());
+    // No gas limit could pay for memory past 32 bits, so bail out before the cost overflows.
+    let Some(end) = offset
+        .checked_add(size)
+        .filter(|end| *end <= u32::MAX.into())
+    else {
+        gas.consume_all();
+        return Err(ExecutionError::OutOfGas);
+    };
+    let end = end.as_usize();
+
+    let current_words = gas::words(memory.len());
+    let new_words = gas::words(end);
+    if new_words > current_words {
+        gas.charge(gas::memory_cost(new_words) - gas::memory_cost(current_words))?;
+        memory.resize(new_words as usize * 32, 0);
     }
 
-    bytes
+    Ok((offset.as_usize(), size.as_usize()))
 }
 
-pub fn read_memory(memory: &mut Vec<u8>, offset: usize) -> U256 {
-    let current_size = memory.len();
-    let new_size = offset + 32 - 1;
-    if new_size >= current_size {
-        expand_memory(memory, new_size);
+/// Reads `size` bytes of `source` from `offset`, yielding zeros past its end.
+pub fn read_padded(source: &[u8], offset: U256, size: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; size];
+    if offset < source.len().into() {
+        let offset = offset.as_usize();
+        let end = min(offset + size, source.len());
+        buf[..end - offset].copy_from_slice(&source[offset..end]);
     }
 
-    let mut value = U256::zero();
-    for i in offset..=new_size {
-        value <<= 8;
-        value += memory[i].into();
-    }
+    buf
+}
 
-    value
+pub fn read_memory_bytes(memory: &[u8], offset: usize, size: usize) -> Vec<u8> {
+    memory[offset..(offset + size)].to_vec()
 }
 
-pub fn write_memory(memory: &mut Vec<u8>, offset: usize, bytes: impl IntoIterator<Item = u8>) {
-    let current_size = memory.len();
-    let bytes = bytes.into_iter().collect::<Vec<_>>();
-    let new_size = offset + bytes.len() - 1;
-    if new_size >= current_size {
-        expand_memory(memory, new_size);
-    }
+pub fn read_memory(memory: &[u8], offset: usize) -> U256 {
+    U256::from_big_endian(&memory[offset..(offset + 32)])
+}
 
-    bytes
-        .into_iter()
-        .enumerate()
-        .for_each(move |(i, byte)| memory[offset + i] = byte);
+pub fn write_memory(memory: &mut [u8], offset: usize, bytes: &[u8]) {
+    memory[offset..(offset + bytes.len())].copy_from_slice(bytes);
 }
 
-pub fn memstore(stack: &mut Vec<U256>, memory: &mut Vec<u8>) {
-    let offset = stack.pop().unwrap().as_usize();
-    let word = stack.pop().unwrap();
+pub fn memstore(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
+    let word = stack.pop()?;
+    let (offset, _) = expand_memory(memory, offset, 32.into(), gas)?;
 
     let mut word_bytes = [0u8; 32];
     word.to_big_endian(&mut word_bytes);
 
-    write_memory(memory, offset, word_bytes)
+    write_memory(memory, offset, &word_bytes);
+    Ok(())
 }
 
-pub fn memstore8(stack: &mut Vec<U256>, memory: &mut Vec<u8>) {
-    let offset = stack.pop().unwrap().as_usize();
-    let word = (stack.pop().unwrap() << 248) >> 248;
-    let byte: u8 = word.try_into().unwrap();
-
-    write_memory(memory, offset, [byte])
+pub fn memstore8(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
+    let byte = stack.pop()?.byte(0);
+    let (offset, _) = expand_memory(memory, offset, 1.into(), gas)?;
+
+    write_memory(memory, offset, &[byte]);
+    Ok(())
 }
 
-pub fn memload(stack: &mut Vec<U256>, memory: &mut Vec<u8>) {
-    let offset = stack.pop().unwrap().as_usize();
+pub fn memload(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
+    let (offset, _) = expand_memory(memory, offset, 32.into(), gas)?;
 
     let value = read_memory(memory, offset);
 
-    stack.push(value);
+    stack.push(value)
 }
 
-pub fn memsize(stack: &mut Vec<U256>, memory: &[u8]) {
-    stack.push(memory.len().into());
+pub fn memcopy(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let dest_offset = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let (offset, _) = expand_memory(memory, offset, size, gas)?;
+    let (dest_offset, size) = expand_memory(memory, dest_offset, size, gas)?;
+    gas.charge(gas::COPY * gas::words(size))?;
+
+    // copy_within behaves as if through a temporary buffer, so the ranges may overlap
+    memory.copy_within(offset..offset + size, dest_offset);
+    Ok(())
 }
 
+pub fn memsize(stack: &mut Stack, memory: &[u8]) -> Result<(), ExecutionError> {
+    stack.push(memory.len().into())
+}
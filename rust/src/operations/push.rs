@@ -1,28 +1,30 @@
 use primitive_types::U256;
 
-pub fn push(from_byte: usize, n_bytes: usize, bytes: &Vec<u8>, stack: &mut Vec<U256>) {
+use crate::error::ExecutionError;
+use crate::stack::Stack;
+
+/// Pushes the `n_bytes` immediate bytes starting at `from_byte`; code past its end reads as zeros.
+pub fn push(
+    from_byte: usize,
+    n_bytes: usize,
+    bytes: &[u8],
+    stack: &mut Stack,
+) -> Result<(), ExecutionError> {
     let mut value = U256::zero();
     for i in from_byte..(from_byte + n_bytes) {
         value <<= 8;
-        value += bytes[i].into();
+        value += bytes.get(i).copied().unwrap_or_default().into();
     }
 
-    stack.push(value);
+    stack.push(value)
 }
 
-pub fn duplicate(offset: usize, stack: &mut Vec<U256>) {
-    let value_idx: usize = stack.len() - offset;
-    let value = stack[value_idx].clone();
+pub fn duplicate(offset: usize, stack: &mut Stack) -> Result<(), ExecutionError> {
+    let value = stack.peek(offset)?;
 
-    stack.push(value);
+    stack.push(value)
 }
 
-pub fn swap(offset: usize, stack: &mut Vec<U256>) {
-    let value_idx: usize = stack.len() - offset - 1;
-    let value = stack[value_idx].clone();
-
-    let top_value = stack.pop().unwrap();
-    stack.push(value);
-    stack[value_idx] = top_value;
+pub fn swap(offset: usize, stack: &mut Stack) -> Result<(), ExecutionError> {
+    stack.swap(offset)
 }
-
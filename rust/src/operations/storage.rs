@@ -1,19 +1,122 @@
-use std::collections::HashMap;
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    message::Message,
+    spec::SpecId,
+    stack::Stack,
+    state::BlockchainState,
+};
 
-use primitive_types::U256;
+/// Net gas metering for SSTORE (EIP-2200, with the EIP-2929 and EIP-3529 amounts once they
+/// apply). Before Istanbul, only the current and new values set the price.
+pub fn storage_store(
+    stack: &mut Stack,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let key = stack.pop()?;
+    let value = stack.pop()?;
 
-pub fn storage_store(stack: &mut Vec<U256>, storage: &mut HashMap<U256, U256>) {
-    let key = stack.pop().unwrap();
-    let value = stack.pop().unwrap();
+    let net_metering = spec.enabled(SpecId::Istanbul);
+    if net_metering && gas.remaining() <= gas::CALL_STIPEND {
+        return Err(ExecutionError::OutOfGas);
+    }
 
-    storage.insert(key, value);
+    let address = message.address;
+    if chain_state.warm_storage_key(address, key) && spec.enabled(SpecId::Berlin) {
+        gas.charge(gas::COLD_SLOAD)?;
+    }
+
+    let current = chain_state.storage_value(address, key);
+    let original = chain_state.original_storage_value(address, key, current);
+
+    let noop = gas::sload_cost(spec);
+    let reset = gas::sstore_reset_cost(spec);
+    let clears_schedule = gas::sstore_clears_schedule(spec);
+
+    if !net_metering {
+        if current.is_zero() && !value.is_zero() {
+            gas.charge(gas::SSTORE_SET)?;
+        } else {
+            gas.charge(reset)?;
+            if !current.is_zero() && value.is_zero() {
+                chain_state.add_refund(clears_schedule);
+            }
+        }
+    } else if value == current {
+        gas.charge(noop)?;
+    } else if original == current {
+        if original.is_zero() {
+            gas.charge(gas::SSTORE_SET)?;
+        } else {
+            gas.charge(reset)?;
+            if value.is_zero() {
+                chain_state.add_refund(clears_schedule);
+            }
+        }
+    } else {
+        gas.charge(noop)?;
+
+        if !original.is_zero() {
+            if current.is_zero() {
+                chain_state.sub_refund(clears_schedule);
+            } else if value.is_zero() {
+                chain_state.add_refund(clears_schedule);
+            }
+        }
+
+        if original == value {
+            chain_state.add_refund(if original.is_zero() {
+                gas::SSTORE_SET - noop
+            } else {
+                reset - noop
+            });
+        }
+    }
+
+    chain_state.set_storage_value(address, key, value);
+    Ok(())
 }
 
+pub fn storage_load(
+    stack: &mut Stack,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<(), ExecutionError> {
+    let key = stack.pop()?;
 
-pub fn storage_load(stack: &mut Vec<U256>, storage: &mut HashMap<U256, U256>) {
-    let key = stack.pop().unwrap();
+    let address = message.address;
+    if chain_state.warm_storage_key(address, key) && spec.enabled(SpecId::Berlin) {
+        gas.charge(gas::COLD_SLOAD - gas::WARM_STORAGE_READ)?;
+    }
+
+    let value = chain_state.storage_value(address, key);
+
+    stack.push(value)
+}
+
+pub fn transient_store(
+    stack: &mut Stack,
+    chain_state: &mut BlockchainState,
+    message: &Message,
+) -> Result<(), ExecutionError> {
+    let key = stack.pop()?;
+    let value = stack.pop()?;
+
+    chain_state.set_transient_storage_value(message.address, key, value);
+    Ok(())
+}
 
-    let value = storage.get(&key).unwrap_or(&U256::zero()).clone();
+pub fn transient_load(
+    stack: &mut Stack,
+    chain_state: &BlockchainState,
+    message: &Message,
+) -> Result<(), ExecutionError> {
+    let key = stack.pop()?;
 
-    stack.push(value);
+    stack.push(chain_state.transient_storage_value(message.address, key))
 }
@@ -1,173 +1,223 @@
-use std::mem;
-use std::{cmp::min, collections::HashMap};
-
 use primitive_types::U256;
 
 use crate::{
-    evm,
-    state::{BlockchainState, SerializedBytes, SerializedU256},
+    error::ExecutionError,
+    frame::{Frame, FrameKind},
+    gas::{self, Gas},
+    message::{Message, CALL_DEPTH_LIMIT},
+    spec::SpecId,
+    stack::Stack,
+    state::{BlockchainState, SerializedU256},
 };
 
-use super::{read_memory_bytes, write_memory};
-
-pub fn call_value(stack: &mut Vec<U256>, chain_state: &mut BlockchainState) {
-    let value: U256 = chain_state.tx.value.unwrap_or_default().into();
+use super::{expand_memory, read_memory_bytes, read_padded, write_memory};
 
-    stack.push(value);
+pub fn call_value(stack: &mut Stack, message: &Message) -> Result<(), ExecutionError> {
+    stack.push(message.value)
 }
 
-pub fn call_data_load(stack: &mut Vec<U256>, chain_state: &mut BlockchainState) {
-    let offset = stack.pop().unwrap().as_usize();
-    let data: Vec<u8> = chain_state.tx.data.clone().unwrap_or_default().into();
+pub fn call_data_load(stack: &mut Stack, message: &Message) -> Result<(), ExecutionError> {
+    let offset = stack.pop()?;
 
-    let mut buf = vec![0u8; 32];
-    let end = min(offset + 32, data.len());
-    data[offset..end].clone_into(&mut buf);
+    let buf = read_padded(&message.input, offset, 32);
 
-    let buf_len = buf.len();
-    if buf_len < 32 {
-        buf.append(&mut vec![0u8; 32 - buf_len])
-    }
+    stack.push(U256::from_big_endian(&buf))
+}
 
-    stack.push(U256::from_big_endian(&buf));
+pub fn call_data_size(stack: &mut Stack, message: &Message) -> Result<(), ExecutionError> {
+    stack.push(message.input.len().into())
 }
 
-pub fn call_data_size(stack: &mut Vec<U256>, chain_state: &mut BlockchainState) {
-    let data = chain_state.tx.data.clone();
-    let size = data.map(|ref data| data.len()).unwrap_or_default();
+pub fn call_data_copy(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    message: &Message,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let memory_dest_offset = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let (memory_dest_offset, size) = expand_memory(memory, memory_dest_offset, size, gas)?;
+    gas.charge(gas::COPY * gas::words(size))?;
 
-    stack.push(size.into());
+    let buf = read_padded(&message.input, offset, size);
+
+    write_memory(memory, memory_dest_offset, &buf);
+    Ok(())
 }
 
-pub fn call_data_copy(
-    stack: &mut Vec<U256>,
+pub fn return_data_copy(
+    stack: &mut Stack,
     memory: &mut Vec<u8>,
-    chain_state: &mut BlockchainState,
-) {
-    let data: Vec<u8> = chain_state.tx.data.clone().unwrap_or_default().into();
-    let memory_dest_offset = stack.pop().unwrap().as_usize();
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
-
-    let mut buf = vec![0u8; size];
-    let end = min(offset + size, data.len());
-    data[offset..end].clone_into(&mut buf);
-
-    let buf_len = buf.len();
-    if buf_len < 32 {
-        buf.append(&mut vec![0u8; 64 - buf_len])
+    return_data: Option<&Vec<u8>>,
+    gas: &mut Gas,
+) -> Result<(), ExecutionError> {
+    let memory_dest_offset = stack.pop()?;
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    // unlike the other copies, reading past the end of the return data is an error (EIP-211)
+    let return_data = return_data.map(Vec::as_slice).unwrap_or_default();
+    if offset
+        .checked_add(size)
+        .is_none_or(|end| end > return_data.len().into())
+    {
+        return Err(ExecutionError::ReturnDataOutOfBounds);
     }
 
-    write_memory(memory, memory_dest_offset, buf)
+    let (memory_dest_offset, size) = expand_memory(memory, memory_dest_offset, size, gas)?;
+    gas.charge(gas::COPY * gas::words(size))?;
+
+    let buf = read_padded(return_data, offset, size);
+
+    write_memory(memory, memory_dest_offset, &buf);
+    Ok(())
 }
 
-pub fn return_value(stack: &mut Vec<U256>, memory: &mut Vec<u8>) -> Vec<u8> {
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
+pub fn return_value(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<Vec<u8>, ExecutionError> {
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
+
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
 
-    read_memory_bytes(memory, offset, size)
+    Ok(read_memory_bytes(memory, offset, size))
 }
 
-pub fn revert_context(stack: &mut Vec<U256>, memory: &mut Vec<u8>) -> Vec<u8> {
-    let offset = stack.pop().unwrap().as_usize();
-    let size = stack.pop().unwrap().as_usize();
+pub fn revert_context(
+    stack: &mut Stack,
+    memory: &mut Vec<u8>,
+    gas: &mut Gas,
+) -> Result<Vec<u8>, ExecutionError> {
+    let offset = stack.pop()?;
+    let size = stack.pop()?;
 
+    let (offset, size) = expand_memory(memory, offset, size, gas)?;
     let value = read_memory_bytes(memory, offset, size);
 
-    stack.iter().for_each(mem::drop);
-
-    value
+    Ok(value)
 }
 
 #[derive(PartialEq, Eq)]
-pub enum ContextType<'a> {
+pub enum ContextType {
     Writeable,
-    WriteableDelegate(&'a mut HashMap<U256, U256>),
+    WriteableDelegate,
+    CallCode,
     Static,
 }
 
 pub fn call_context(
-    stack: &mut Vec<U256>,
+    stack: &mut Stack,
     memory: &mut Vec<u8>,
     chain_state: &mut BlockchainState,
-    context_type: ContextType<'_>,
-    parent_context_is_static: bool,
-) -> Option<Vec<u8>> {
-    let _gas = stack.pop().unwrap();
-    let address = stack
-        .pop()
-        .map(|address| SerializedU256 { value: address })
-        .unwrap();
-
-    if context_type == ContextType::Writeable {
-        let _value = stack.pop().unwrap();
-
-        // TODO: disallow CALL operation if parent context is static and value != 0
-        // if parent_context_is_static && value != U256::zero() {
-        //     return Some(revert_context(stack, memory));
-        // }
-    }
-
-    let args_offset = stack.pop().unwrap().as_usize();
-    let args_size = stack.pop().unwrap().as_usize();
-
-    let ret_offset = stack.pop().unwrap().as_usize();
-    let _ret_size = stack.pop().unwrap().as_usize();
-
-    let code: Vec<u8> = chain_state
-        .contracts_state
-        .get(&address)
-        .unwrap()
-        .code
-        .clone()
-        .unwrap()
-        .bin
-        .unwrap()
-        .into();
-
-    let new_call_data = if args_size > 0 {
-        let args = read_memory_bytes(memory, args_offset, args_size);
-        Some(SerializedBytes { value: args })
-    } else {
-        None
+    message: &Message,
+    context_type: ContextType,
+    gas: &mut Gas,
+    spec: SpecId,
+) -> Result<Option<Frame>, ExecutionError> {
+    let requested_gas = stack.pop()?;
+    let address = SerializedU256 {
+        value: stack.pop()?,
     };
 
-    let previous_call_data = chain_state.tx.data.clone();
-    let previous_address = chain_state.tx.to;
-    let previous_caller = chain_state.tx.from;
+    let value = match context_type {
+        ContextType::Writeable | ContextType::CallCode => stack.pop()?,
+        ContextType::WriteableDelegate | ContextType::Static => U256::zero(),
+    };
 
-    if context_type == ContextType::Writeable || context_type == ContextType::Static {
-        chain_state.tx.to = Some(address);
-        chain_state.tx.from = previous_address;
+    // CALLCODE sends the value back to the current account, which changes nothing
+    if message.is_static && context_type == ContextType::Writeable && !value.is_zero() {
+        return Err(ExecutionError::WriteProtection);
     }
 
-    chain_state.tx.data = new_call_data;
+    let args_offset = stack.pop()?;
+    let args_size = stack.pop()?;
 
-    let is_new_context_static = context_type == ContextType::Static;
-    let storage: &mut HashMap<U256, U256> =
-        if let ContextType::WriteableDelegate(storage) = context_type {
-            storage
-        } else {
-            &mut HashMap::new()
-        };
+    let ret_offset = stack.pop()?;
+    let ret_size = stack.pop()?;
 
-    let res = evm(&code, memory, storage, chain_state, is_new_context_static);
-    let has_reverted = !res.success;
+    let (args_offset, args_size) = expand_memory(memory, args_offset, args_size, gas)?;
+    let (ret_offset, ret_size) = expand_memory(memory, ret_offset, ret_size, gas)?;
 
-    if let Some(ref ret) = res.ret {
-        write_memory(memory, ret_offset, ret.clone()); // TODO: check return size
+    let is_cold = chain_state.warm_address(address);
+    gas.charge(gas::account_access_cost(is_cold, spec))?;
+
+    // before EIP-161, calling an account that does not exist yet was charged even without value
+    let creates_account = if spec.enabled(SpecId::SpuriousDragon) {
+        !value.is_zero() && chain_state.is_empty_account(address)
+    } else {
+        !chain_state.account_exists(address)
+    };
+    if context_type == ContextType::Writeable && creates_account {
+        gas.charge(gas::CALL_NEW_ACCOUNT)?;
     }
 
-    chain_state.tx.data = previous_call_data;
-    chain_state.tx.from = previous_caller;
-    chain_state.tx.to = previous_address;
+    let mut stipend = 0;
+    if !value.is_zero() {
+        gas.charge(gas::CALL_VALUE)?;
+        stipend = gas::CALL_STIPEND;
+    }
 
-    let ret_code = if has_reverted {
-        U256::zero()
+    let call_gas = if spec.enabled(SpecId::TangerineWhistle) {
+        gas.forward(requested_gas)
     } else {
-        U256::one()
+        gas.forward_exact(requested_gas)?
+    } + stipend;
+    if message.depth >= CALL_DEPTH_LIMIT || chain_state.balance(message.address) < value {
+        gas.reclaim(call_gas);
+        stack.push(U256::zero())?;
+        return Ok(None);
+    }
+
+    let code = chain_state.code(address);
+    let input = read_memory_bytes(memory, args_offset, args_size);
+
+    // a delegate call runs the code of `address` on behalf of the current frame, and a call
+    // code does the same but as a call from the current account, with its own value
+    let child_message = match context_type {
+        ContextType::WriteableDelegate => Message {
+            code_address: address,
+            input,
+            gas: call_gas,
+            depth: message.depth + 1,
+            ..message.clone()
+        },
+        ContextType::CallCode => Message {
+            caller: message.address,
+            address: message.address,
+            code_address: address,
+            value,
+            input,
+            gas: call_gas,
+            is_static: message.is_static,
+            depth: message.depth + 1,
+        },
+        ContextType::Writeable | ContextType::Static => Message {
+            caller: message.address,
+            address,
+            code_address: address,
+            value,
+            input,
+            gas: call_gas,
+            is_static: message.is_static || context_type == ContextType::Static,
+            depth: message.depth + 1,
+        },
     };
-    stack.push(ret_code);
 
-    res.ret
+    let checkpoint = chain_state.checkpoint();
+    chain_state.transfer(message.address, child_message.address, value);
+
+    Ok(Some(Frame::new(
+        child_message,
+        code,
+        FrameKind::Call {
+            ret_offset,
+            ret_size,
+        },
+        checkpoint,
+    )))
 }
@@ -0,0 +1,281 @@
+use ark_bls12_381::{g1, g2, Bls12_381, Fq, Fq2, Fr};
+use ark_ec::{
+    hashing::{curve_maps::wb::WBMap, map_to_curve_hasher::MapToCurve},
+    pairing::Pairing,
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+    AffineRepr, CurveGroup, VariableBaseMSM,
+};
+use ark_ff::{BigInt, BigInteger, One, PrimeField, Zero};
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+};
+
+/// Size of an element of the base field: 48 bytes, left-padded with zeros to 64.
+const FP_SIZE: usize = 64;
+const FP_PADDING: usize = 16;
+/// Size of a point of G1: its two coordinates.
+const G1_SIZE: usize = 2 * FP_SIZE;
+/// Size of a point of G2: its two coordinates, each of them two elements of the base field.
+const G2_SIZE: usize = 4 * FP_SIZE;
+const SCALAR_SIZE: usize = 32;
+
+/// Discount on the price per point of a multi-scalar multiplication, in thousandths, by number
+/// of points. Past the end of the table, the last discount applies.
+const G1_MSM_DISCOUNTS: [u64; 128] = [
+    1000, 949, 848, 797, 764, 750, 738, 728, 719, 712, 705, 698, 692, 687, 682, 677, 673, 669, 665,
+    661, 658, 654, 651, 648, 645, 642, 640, 637, 635, 632, 630, 627, 625, 623, 621, 619, 617, 615,
+    613, 611, 609, 608, 606, 604, 603, 601, 599, 598, 596, 595, 593, 592, 591, 589, 588, 586, 585,
+    584, 582, 581, 580, 579, 577, 576, 575, 574, 573, 572, 570, 569, 568, 567, 566, 565, 564, 563,
+    562, 561, 560, 559, 558, 557, 556, 555, 554, 553, 552, 551, 550, 549, 548, 547, 547, 546, 545,
+    544, 543, 542, 541, 540, 540, 539, 538, 537, 536, 536, 535, 534, 533, 532, 532, 531, 530, 529,
+    528, 528, 527, 526, 525, 525, 524, 523, 522, 522, 521, 520, 520, 519,
+];
+const G2_MSM_DISCOUNTS: [u64; 128] = [
+    1000, 1000, 923, 884, 855, 832, 812, 796, 782, 770, 759, 749, 740, 732, 724, 717, 711, 704,
+    699, 693, 688, 683, 679, 674, 670, 666, 663, 659, 655, 652, 649, 646, 643, 640, 637, 634, 632,
+    629, 627, 624, 622, 620, 618, 615, 613, 611, 609, 607, 606, 604, 602, 600, 598, 597, 595, 593,
+    592, 590, 589, 587, 586, 584, 583, 582, 580, 579, 578, 576, 575, 574, 573, 571, 570, 569, 568,
+    567, 566, 565, 563, 562, 561, 560, 559, 558, 557, 556, 555, 554, 553, 552, 552, 551, 550, 549,
+    548, 547, 546, 545, 545, 544, 543, 542, 541, 541, 540, 539, 538, 537, 537, 536, 535, 535, 534,
+    533, 532, 532, 531, 530, 530, 529, 528, 528, 527, 526, 526, 525, 524, 524,
+];
+
+/// Sum of two points of G1 (0x0b, EIP-2537). They only need to be on the curve.
+pub fn bls12_g1add(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::BLS12_G1ADD)?;
+    if input.len() != 2 * G1_SIZE {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let p = read_g1(&input[..G1_SIZE])?;
+    let q = read_g1(&input[G1_SIZE..])?;
+
+    Ok(encode_g1((p + q).into_affine()))
+}
+
+/// Sum of the products of points of G1 by scalars (0x0c, EIP-2537).
+pub fn bls12_g1msm(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    let sum = msm(
+        input,
+        gas,
+        G1_SIZE,
+        gas::BLS12_G1MSM,
+        &G1_MSM_DISCOUNTS,
+        read_g1,
+    )?;
+
+    Ok(encode_g1(sum))
+}
+
+/// Sum of two points of G2 (0x0d, EIP-2537). They only need to be on the curve.
+pub fn bls12_g2add(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::BLS12_G2ADD)?;
+    if input.len() != 2 * G2_SIZE {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let p = read_g2(&input[..G2_SIZE])?;
+    let q = read_g2(&input[G2_SIZE..])?;
+
+    Ok(encode_g2((p + q).into_affine()))
+}
+
+/// Sum of the products of points of G2 by scalars (0x0e, EIP-2537).
+pub fn bls12_g2msm(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    let sum = msm(
+        input,
+        gas,
+        G2_SIZE,
+        gas::BLS12_G2MSM,
+        &G2_MSM_DISCOUNTS,
+        read_g2,
+    )?;
+
+    Ok(encode_g2(sum))
+}
+
+/// Whether the product of the pairings of every (G1, G2) pair in the input is one
+/// (0x0f, EIP-2537).
+pub fn bls12_pairing_check(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    let pair_size = G1_SIZE + G2_SIZE;
+    if input.is_empty() || !input.len().is_multiple_of(pair_size) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let n_pairs = (input.len() / pair_size) as u64;
+    gas.charge(gas::BLS12_PAIRING + gas::BLS12_PAIRING_PAIR * n_pairs)?;
+
+    let mut g1_points = Vec::new();
+    let mut g2_points = Vec::new();
+    for pair in input.chunks(pair_size) {
+        g1_points.push(in_subgroup(read_g1(&pair[..G1_SIZE])?)?);
+        g2_points.push(in_subgroup(read_g2(&pair[G1_SIZE..])?)?);
+    }
+
+    let mut output = vec![0u8; 32];
+    if Bls12_381::multi_pairing(g1_points, g2_points).0.is_one() {
+        output[31] = 1;
+    }
+
+    Ok(output)
+}
+
+/// Point of G1 that an element of the base field maps to (0x10, EIP-2537).
+pub fn bls12_map_fp_to_g1(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::BLS12_MAP_FP_TO_G1)?;
+    if input.len() != FP_SIZE {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let element = read_fp(input)?;
+    let point = WBMap::<g1::Config>::map_to_curve(element)
+        .map_err(|_| ExecutionError::InvalidPrecompileInput)?;
+
+    Ok(encode_g1(point.clear_cofactor()))
+}
+
+/// Point of G2 that an element of the quadratic extension field maps to (0x11, EIP-2537).
+pub fn bls12_map_fp2_to_g2(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::BLS12_MAP_FP2_TO_G2)?;
+    if input.len() != 2 * FP_SIZE {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let element = read_fp2(input)?;
+    let point = WBMap::<g2::Config>::map_to_curve(element)
+        .map_err(|_| ExecutionError::InvalidPrecompileInput)?;
+
+    Ok(encode_g2(point.clear_cofactor()))
+}
+
+/// Multi-scalar multiplication over pairs of a point, which must be in the prime order
+/// subgroup, and a 32-byte scalar.
+fn msm<P: SWCurveConfig<ScalarField = Fr>>(
+    input: &[u8],
+    gas: &mut Gas,
+    point_size: usize,
+    cost_per_point: u64,
+    discounts: &[u64],
+    read_point: fn(&[u8]) -> Result<Affine<P>, ExecutionError>,
+) -> Result<Affine<P>, ExecutionError> {
+    let pair_size = point_size + SCALAR_SIZE;
+    if input.is_empty() || !input.len().is_multiple_of(pair_size) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let n_points = input.len() / pair_size;
+    let discount = discounts
+        .get(n_points - 1)
+        .or(discounts.last())
+        .copied()
+        .unwrap_or_default();
+    gas.charge(n_points as u64 * cost_per_point * discount / 1000)?;
+
+    let mut points = Vec::new();
+    let mut scalars = Vec::new();
+    for pair in input.chunks(pair_size) {
+        points.push(in_subgroup(read_point(&pair[..point_size])?)?);
+        // points of the subgroup have order r, so reducing the scalar does not change them
+        scalars.push(Fr::from_be_bytes_mod_order(&pair[point_size..]));
+    }
+
+    let sum = Projective::<P>::msm(&points, &scalars)
+        .map_err(|_| ExecutionError::InvalidPrecompileInput)?;
+
+    Ok(sum.into_affine())
+}
+
+/// Element of the base field, which must be below the modulus.
+fn read_fp(bytes: &[u8]) -> Result<Fq, ExecutionError> {
+    let (padding, value) = bytes.split_at(FP_PADDING);
+    if padding.iter().any(|byte| *byte != 0) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let mut limbs = [0u64; 6];
+    for (limb, chunk) in limbs.iter_mut().zip(value.rchunks_exact(8)) {
+        // chunks of exactly 8 bytes always convert
+        *limb = u64::from_be_bytes(chunk.try_into().unwrap());
+    }
+
+    Fq::from_bigint(BigInt::new(limbs)).ok_or(ExecutionError::InvalidPrecompileInput)
+}
+
+/// Element of the quadratic extension field encoded as c0 ++ c1, for c0 + c1 * u.
+fn read_fp2(bytes: &[u8]) -> Result<Fq2, ExecutionError> {
+    Ok(Fq2::new(
+        read_fp(&bytes[..FP_SIZE])?,
+        read_fp(&bytes[FP_SIZE..])?,
+    ))
+}
+
+/// Point of G1 encoded as x ++ y, where (0, 0) stands for the point at infinity.
+fn read_g1(bytes: &[u8]) -> Result<Affine<g1::Config>, ExecutionError> {
+    let x = read_fp(&bytes[..FP_SIZE])?;
+    let y = read_fp(&bytes[FP_SIZE..])?;
+
+    on_curve(x, y)
+}
+
+/// Point of G2 encoded as x ++ y, where (0, 0) stands for the point at infinity.
+fn read_g2(bytes: &[u8]) -> Result<Affine<g2::Config>, ExecutionError> {
+    let x = read_fp2(&bytes[..2 * FP_SIZE])?;
+    let y = read_fp2(&bytes[2 * FP_SIZE..])?;
+
+    on_curve(x, y)
+}
+
+fn on_curve<P: SWCurveConfig>(
+    x: P::BaseField,
+    y: P::BaseField,
+) -> Result<Affine<P>, ExecutionError> {
+    if x.is_zero() && y.is_zero() {
+        return Ok(Affine::identity());
+    }
+
+    let point = Affine::new_unchecked(x, y);
+    if !point.is_on_curve() {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    Ok(point)
+}
+
+/// Rejects points on the curve that are outside of the subgroup of prime order r.
+fn in_subgroup<P: SWCurveConfig>(point: Affine<P>) -> Result<Affine<P>, ExecutionError> {
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    Ok(point)
+}
+
+fn encode_fp(element: Fq, out: &mut [u8]) {
+    out[FP_PADDING..].copy_from_slice(&element.into_bigint().to_bytes_be());
+}
+
+fn encode_g1(point: Affine<g1::Config>) -> Vec<u8> {
+    let mut output = vec![0u8; G1_SIZE];
+
+    // the point at infinity has no affine coordinates and is encoded as (0, 0)
+    if let Some((x, y)) = point.xy() {
+        encode_fp(x, &mut output[..FP_SIZE]);
+        encode_fp(y, &mut output[FP_SIZE..]);
+    }
+
+    output
+}
+
+fn encode_g2(point: Affine<g2::Config>) -> Vec<u8> {
+    let mut output = vec![0u8; G2_SIZE];
+
+    if let Some((x, y)) = point.xy() {
+        for (i, element) in [x.c0, x.c1, y.c0, y.c1].into_iter().enumerate() {
+            encode_fp(element, &mut output[i * FP_SIZE..(i + 1) * FP_SIZE]);
+        }
+    }
+
+    output
+}
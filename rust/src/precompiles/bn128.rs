@@ -0,0 +1,144 @@
+use primitive_types::U256;
+use substrate_bn::{AffineG1, AffineG2, Fq, Fq2, Fr, Group, Gt, G1, G2};
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    operations::read_padded,
+};
+
+/// Size of a point of G1: its two coordinates.
+const G1_SIZE: usize = 64;
+/// Size of a pair checked by ECPAIRING: a point of G1 followed by a point of G2.
+const PAIR_SIZE: usize = G1_SIZE + 128;
+
+/// Sum of two points of the alt_bn128 curve (0x06, EIP-196), at Istanbul prices (EIP-1108).
+pub fn ecadd(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    add(input, gas, gas::ECADD)
+}
+
+pub fn ecadd_byzantium(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    add(input, gas, gas::ECADD_BYZANTIUM)
+}
+
+/// Product of a point of the alt_bn128 curve by a scalar (0x07, EIP-196), at Istanbul prices.
+pub fn ecmul(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    mul(input, gas, gas::ECMUL)
+}
+
+pub fn ecmul_byzantium(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    mul(input, gas, gas::ECMUL_BYZANTIUM)
+}
+
+/// Whether the product of the pairings of every (G1, G2) pair in the input is one
+/// (0x08, EIP-197), at Istanbul prices.
+pub fn ecpairing(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    pairing(input, gas, gas::ECPAIRING, gas::ECPAIRING_PAIR)
+}
+
+pub fn ecpairing_byzantium(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    pairing(
+        input,
+        gas,
+        gas::ECPAIRING_BYZANTIUM,
+        gas::ECPAIRING_PAIR_BYZANTIUM,
+    )
+}
+
+fn add(input: &[u8], gas: &mut Gas, cost: u64) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(cost)?;
+
+    let input = read_padded(input, U256::zero(), 2 * G1_SIZE);
+    let p = read_g1(&input[..G1_SIZE])?;
+    let q = read_g1(&input[G1_SIZE..])?;
+
+    Ok(encode_g1(p + q))
+}
+
+fn mul(input: &[u8], gas: &mut Gas, cost: u64) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(cost)?;
+
+    let input = read_padded(input, U256::zero(), G1_SIZE + 32);
+    let p = read_g1(&input[..G1_SIZE])?;
+    let scalar =
+        Fr::from_slice(&input[G1_SIZE..]).map_err(|_| ExecutionError::InvalidPrecompileInput)?;
+
+    Ok(encode_g1(p * scalar))
+}
+
+fn pairing(
+    input: &[u8],
+    gas: &mut Gas,
+    cost: u64,
+    cost_per_pair: u64,
+) -> Result<Vec<u8>, ExecutionError> {
+    if !input.len().is_multiple_of(PAIR_SIZE) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let n_pairs = (input.len() / PAIR_SIZE) as u64;
+    gas.charge(cost + cost_per_pair * n_pairs)?;
+
+    let mut pairs = Vec::new();
+    for pair in input.chunks(PAIR_SIZE) {
+        let p = read_g1(&pair[..G1_SIZE])?;
+        let q = read_g2(&pair[G1_SIZE..])?;
+
+        // a pairing with the point at infinity is one, which leaves the product unchanged
+        if !p.is_zero() && !q.is_zero() {
+            pairs.push((p, q));
+        }
+    }
+
+    let mut output = vec![0u8; 32];
+    if substrate_bn::pairing_batch(&pairs) == Gt::one() {
+        output[31] = 1;
+    }
+
+    Ok(output)
+}
+
+fn read_fq(bytes: &[u8]) -> Result<Fq, ExecutionError> {
+    Fq::from_slice(bytes).map_err(|_| ExecutionError::InvalidPrecompileInput)
+}
+
+/// Point of G1 encoded as x ++ y, where (0, 0) stands for the point at infinity.
+fn read_g1(bytes: &[u8]) -> Result<G1, ExecutionError> {
+    let x = read_fq(&bytes[..32])?;
+    let y = read_fq(&bytes[32..64])?;
+
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1::zero());
+    }
+
+    AffineG1::new(x, y)
+        .map(G1::from)
+        .map_err(|_| ExecutionError::InvalidPrecompileInput)
+}
+
+/// Point of G2 encoded as x ++ y, each coordinate having its imaginary part first.
+fn read_g2(bytes: &[u8]) -> Result<G2, ExecutionError> {
+    let x = Fq2::new(read_fq(&bytes[32..64])?, read_fq(&bytes[..32])?);
+    let y = Fq2::new(read_fq(&bytes[96..128])?, read_fq(&bytes[64..96])?);
+
+    if x.is_zero() && y.is_zero() {
+        return Ok(G2::zero());
+    }
+
+    AffineG2::new(x, y)
+        .map(G2::from)
+        .map_err(|_| ExecutionError::InvalidPrecompileInput)
+}
+
+fn encode_g1(point: G1) -> Vec<u8> {
+    let mut output = vec![0u8; G1_SIZE];
+
+    // the point at infinity has no affine coordinates and is encoded as (0, 0)
+    if let Some(point) = AffineG1::from_jacobian(point) {
+        // both coordinates are 32 bytes long, so they always fit
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+
+    output
+}
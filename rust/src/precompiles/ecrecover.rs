@@ -0,0 +1,47 @@
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use primitive_types::U256;
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    operations::{hash, read_padded},
+};
+
+/// Address that signed the hash `input[0..32]` with the signature `v`, `r`, `s` found in
+/// `input[32..128]` (0x01). An invalid signature is not an error, it returns nothing.
+pub fn ecrecover(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::ECRECOVER)?;
+
+    let input = read_padded(input, U256::zero(), 128);
+    Ok(recover(&input).unwrap_or_default())
+}
+
+fn recover(input: &[u8]) -> Option<Vec<u8>> {
+    let v = U256::from_big_endian(&input[32..64]);
+    let recovery_byte = if v == U256::from(27) {
+        0
+    } else if v == U256::from(28) {
+        1
+    } else {
+        return None;
+    };
+
+    let mut recovery_id = RecoveryId::from_byte(recovery_byte)?;
+    let mut signature = Signature::from_slice(&input[64..128]).ok()?;
+
+    // the EVM accepts signatures with a high s, which k256 only recovers once normalised
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+    }
+
+    let key = VerifyingKey::recover_from_prehash(&input[..32], &signature, recovery_id).ok()?;
+    let public_key = key.to_encoded_point(false);
+
+    // the address is the last 20 bytes of the hash of the uncompressed key, without its prefix
+    let mut address = [0u8; 32];
+    hash(&public_key.as_bytes()[1..]).to_big_endian(&mut address);
+    address[..12].fill(0);
+
+    Some(address.to_vec())
+}
@@ -0,0 +1,23 @@
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+};
+
+/// SHA2-256 digest of the input (0x02).
+pub fn sha256(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::SHA256 + gas::SHA256_WORD * gas::words(input.len()))?;
+
+    Ok(Sha256::digest(input).to_vec())
+}
+
+/// RIPEMD-160 digest of the input, left-padded to a 32-byte word (0x03).
+pub fn ripemd160(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::RIPEMD160 + gas::RIPEMD160_WORD * gas::words(input.len()))?;
+
+    let mut output = vec![0u8; 12];
+    output.extend_from_slice(&Ripemd160::digest(input));
+    Ok(output)
+}
@@ -0,0 +1,11 @@
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+};
+
+/// Returns its input unchanged (0x04).
+pub fn identity(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::IDENTITY + gas::IDENTITY_WORD * gas::words(input.len()))?;
+
+    Ok(input.to_vec())
+}
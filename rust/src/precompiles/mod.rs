@@ -0,0 +1,242 @@
+mod blake2f;
+mod bls12_381;
+mod bn128;
+mod ecrecover;
+mod hash;
+mod identity;
+mod modexp;
+mod point_evaluation;
+
+use std::collections::HashMap;
+
+use primitive_types::U256;
+
+use crate::{
+    error::ExecutionError,
+    frame::{Frame, Outcome},
+    gas::Gas,
+    spec::SpecId,
+};
+
+use blake2f::blake2f;
+use bls12_381::{
+    bls12_g1add, bls12_g1msm, bls12_g2add, bls12_g2msm, bls12_map_fp2_to_g2, bls12_map_fp_to_g1,
+    bls12_pairing_check,
+};
+use bn128::{ecadd, ecadd_byzantium, ecmul, ecmul_byzantium, ecpairing, ecpairing_byzantium};
+use ecrecover::ecrecover;
+use hash::{ripemd160, sha256};
+use identity::identity;
+use modexp::{modexp, modexp_byzantium};
+use point_evaluation::point_evaluation;
+
+/// A contract implemented natively rather than in EVM bytecode.
+pub trait Precompile {
+    /// Computes the output for `input`, spending at most `gas_limit`. An error fails the call to
+    /// the precompile, consuming all the gas forwarded to it, but the caller keeps running.
+    fn run(&self, input: &[u8], gas_limit: u64) -> Result<PrecompileOutput, ExecutionError>;
+}
+
+pub struct PrecompileOutput {
+    pub output: Vec<u8>,
+    pub gas_used: u64,
+}
+
+type BuiltinFn = fn(&[u8], &mut Gas) -> Result<Vec<u8>, ExecutionError>;
+
+/// The precompiles shipped with the interpreter, which charge gas as they go.
+struct Builtin(BuiltinFn);
+
+impl Precompile for Builtin {
+    fn run(&self, input: &[u8], gas_limit: u64) -> Result<PrecompileOutput, ExecutionError> {
+        let mut gas = Gas::new(gas_limit);
+        let output = (self.0)(input, &mut gas)?;
+
+        Ok(PrecompileOutput {
+            output,
+            gas_used: gas.used(),
+        })
+    }
+}
+
+/// Precompiled contracts by address.
+pub struct Precompiles(HashMap<U256, Box<dyn Precompile>>);
+
+impl Default for Precompiles {
+    fn default() -> Self {
+        Precompiles::new(SpecId::default())
+    }
+}
+
+impl Precompiles {
+    /// The standard precompiles that exist in `spec`, priced as they were then.
+    pub fn new(spec: SpecId) -> Self {
+        let mut builtins: Vec<BuiltinFn> = vec![ecrecover, sha256, ripemd160, identity];
+
+        if spec.enabled(SpecId::Berlin) {
+            builtins.push(modexp);
+        } else if spec.enabled(SpecId::Byzantium) {
+            builtins.push(modexp_byzantium);
+        }
+
+        if spec.enabled(SpecId::Istanbul) {
+            builtins.extend([ecadd, ecmul, ecpairing, blake2f]);
+        } else if spec.enabled(SpecId::Byzantium) {
+            builtins.extend([ecadd_byzantium, ecmul_byzantium, ecpairing_byzantium]);
+        }
+
+        if spec.enabled(SpecId::Cancun) {
+            builtins.push(point_evaluation);
+        }
+
+        if spec.enabled(SpecId::Prague) {
+            builtins.extend([
+                bls12_g1add,
+                bls12_g1msm,
+                bls12_g2add,
+                bls12_g2msm,
+                bls12_pairing_check,
+                bls12_map_fp_to_g1,
+                bls12_map_fp2_to_g2,
+            ]);
+        }
+
+        // they are laid out one after the other from 0x01
+        let mut precompiles = Precompiles(HashMap::new());
+        for (address, builtin) in (1u64..).zip(builtins) {
+            precompiles.register(address.into(), Builtin(builtin));
+        }
+
+        precompiles
+    }
+
+    /// Installs `precompile` at `address`, returning the one it replaces, if any.
+    pub fn register(
+        &mut self,
+        address: U256,
+        precompile: impl Precompile + 'static,
+    ) -> Option<Box<dyn Precompile>> {
+        self.0.insert(address, Box::new(precompile))
+    }
+
+    pub fn get(&self, address: U256) -> Option<&dyn Precompile> {
+        self.0.get(&address).map(|precompile| precompile.as_ref())
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = U256> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// Runs `frame` natively if its code address holds a precompile, returning how it halted.
+    pub(crate) fn execute(&self, frame: &mut Frame) -> Option<Outcome> {
+        let precompile = self.get(frame.message.code_address.into())?;
+
+        let result = precompile
+            .run(&frame.message.input, frame.gas.remaining())
+            .and_then(|result| {
+                frame.gas.charge(result.gas_used)?;
+                Ok(result.output)
+            });
+
+        let outcome = match result {
+            Ok(output) => Outcome {
+                success: true,
+                error: None,
+                ret: Some(output),
+            },
+            Err(err) => Outcome::exceptional(err),
+        };
+
+        Some(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gas,
+        message::Message,
+        state::{BlockchainState, TxData},
+        Evm, EvmResult,
+    };
+
+    /// Returns its input reversed, for a flat fee.
+    struct Reverse;
+
+    impl Reverse {
+        const GAS: u64 = 7;
+    }
+
+    impl Precompile for Reverse {
+        fn run(&self, input: &[u8], gas_limit: u64) -> Result<PrecompileOutput, ExecutionError> {
+            if gas_limit < Reverse::GAS {
+                return Err(ExecutionError::OutOfGas);
+            }
+
+            Ok(PrecompileOutput {
+                output: input.iter().rev().copied().collect(),
+                gas_used: Reverse::GAS,
+            })
+        }
+    }
+
+    /// CALLs `address` with `input`, leaving the gas left before and after the CALL and its
+    /// success flag on the stack and returning what it returned.
+    fn call(evm: &Evm, address: U256, input: &[u8]) -> EvmResult {
+        let mut address_bytes = [0u8; 32];
+        address.to_big_endian(&mut address_bytes);
+
+        // CALLDATACOPY the input to memory, then GAS, CALL, GAS and RETURN the output
+        let mut code = hex::decode("3660006000375a366000366000600073").unwrap();
+        code.extend_from_slice(&address_bytes[12..]);
+        code.extend(hex::decode("5af15a3d6000f3").unwrap());
+
+        let tx = TxData {
+            data: Some(input.to_vec().into()),
+            ..TxData::default()
+        };
+        let message = Message::transaction(&tx, 1_000_000);
+        let mut chain_state = BlockchainState::new(tx, Default::default(), Default::default());
+
+        evm.run(&code, &mut Vec::new(), &mut chain_state, &message)
+    }
+
+    /// Gas spent by the precompile itself: the pushes, the warm CALL and GAS take 120.
+    fn precompile_gas(result: &EvmResult) -> u64 {
+        let after = result.stack[0].as_u64();
+        let before = result.stack[2].as_u64();
+        before - after - 120
+    }
+
+    #[test]
+    fn runs_a_registered_precompile() {
+        let address = U256::from(0x100);
+        let mut evm = Evm::new();
+        assert!(evm.register_precompile(address, Reverse).is_none());
+
+        let result = call(&evm, address, &[1, 2, 3]);
+
+        assert!(result.success);
+        assert_eq!(result.stack[1], U256::one());
+        assert_eq!(result.ret, Some(vec![3, 2, 1]));
+        assert_eq!(precompile_gas(&result), Reverse::GAS);
+    }
+
+    #[test]
+    fn replaces_a_builtin_precompile() {
+        let identity = U256::from(4);
+
+        let result = call(&Evm::new(), identity, &[1, 2, 3]);
+        assert_eq!(result.ret, Some(vec![1, 2, 3]));
+        assert_eq!(precompile_gas(&result), gas::IDENTITY + gas::IDENTITY_WORD);
+
+        let mut evm = Evm::new();
+        assert!(evm.register_precompile(identity, Reverse).is_some());
+
+        let result = call(&evm, identity, &[1, 2, 3]);
+        assert!(result.success);
+        assert_eq!(result.ret, Some(vec![3, 2, 1]));
+        assert_eq!(precompile_gas(&result), Reverse::GAS);
+    }
+}
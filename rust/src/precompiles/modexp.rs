@@ -0,0 +1,118 @@
+use std::cmp::max;
+
+use num_bigint::BigUint;
+use primitive_types::U256;
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+    operations::read_padded,
+};
+
+/// `base ** exponent % modulus` for arbitrarily long operands (0x05, EIP-198), at Berlin
+/// prices (EIP-2565). The input holds the three lengths as 32-byte words, followed by the
+/// operands themselves.
+pub fn modexp(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    run(input, gas, berlin_cost)
+}
+
+pub fn modexp_byzantium(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    run(input, gas, byzantium_cost)
+}
+
+/// Runs the exponentiation after charging `cost`, which maps the length of the longest of the
+/// base and modulus, and the number of iterations, to an amount of gas.
+fn run(
+    input: &[u8],
+    gas: &mut Gas,
+    cost: fn(U256, U256) -> U256,
+) -> Result<Vec<u8>, ExecutionError> {
+    let base_len = U256::from_big_endian(&read_padded(input, U256::zero(), 32));
+    let exponent_len = U256::from_big_endian(&read_padded(input, 32.into(), 32));
+    let modulus_len = U256::from_big_endian(&read_padded(input, 64.into(), 32));
+
+    // operands this long could never be paid for
+    let max_len = max(base_len, modulus_len);
+    if max_len > u32::MAX.into() {
+        return Err(ExecutionError::OutOfGas);
+    }
+
+    let iterations = iterations(input, base_len.as_usize(), exponent_len);
+    let cost = cost(max_len, iterations);
+    gas.charge(if cost > u64::MAX.into() {
+        u64::MAX
+    } else {
+        cost.as_u64()
+    })?;
+
+    // having paid for them, the lengths are known to be reasonably small
+    let base_len = base_len.as_usize();
+    let modulus_len = modulus_len.as_usize();
+    if modulus_len == 0 {
+        return Ok(Vec::new());
+    }
+    let exponent_len = exponent_len.as_usize();
+
+    let base = BigUint::from_bytes_be(&read_padded(input, 96.into(), base_len));
+    let exponent =
+        BigUint::from_bytes_be(&read_padded(input, (96 + base_len).into(), exponent_len));
+    let modulus = BigUint::from_bytes_be(&read_padded(
+        input,
+        (96 + base_len + exponent_len).into(),
+        modulus_len,
+    ));
+
+    let mut output = vec![0u8; modulus_len];
+    if modulus != BigUint::ZERO {
+        let result = base.modpow(&exponent, &modulus).to_bytes_be();
+        output[modulus_len - result.len()..].copy_from_slice(&result);
+    }
+
+    Ok(output)
+}
+
+/// Roughly the number of bits of the exponent. Only its first 32 bytes are looked at, the
+/// rest just count as 8 bits each.
+fn iterations(input: &[u8], base_len: usize, exponent_len: U256) -> U256 {
+    let head_len = exponent_len.min(32.into()).as_usize();
+    let head = U256::from_big_endian(&read_padded(input, (96 + base_len).into(), head_len));
+    let head_bits = U256::from(head.bits().saturating_sub(1));
+
+    if exponent_len <= 32.into() {
+        head_bits
+    } else {
+        (exponent_len - 32)
+            .checked_mul(8.into())
+            .and_then(|bits| bits.checked_add(head_bits))
+            .unwrap_or(U256::MAX)
+    }
+}
+
+/// Multiplications are done on 8-byte limbs (EIP-2565).
+fn berlin_cost(max_len: U256, iterations: U256) -> U256 {
+    let words = max_len.as_u64().div_ceil(8);
+    let complexity = U256::from(words * words);
+
+    let cost = complexity
+        .checked_mul(max(iterations, U256::one()))
+        .map(|cost| cost / gas::MODEXP_DIVISOR)
+        .unwrap_or(U256::MAX);
+    max(cost, gas::MODEXP_MIN.into())
+}
+
+/// Multiplications are priced as in the Karatsuba algorithm (EIP-198).
+fn byzantium_cost(max_len: U256, iterations: U256) -> U256 {
+    let x = max_len;
+    let complexity = if x <= 64.into() {
+        x * x
+    } else if x <= 1024.into() {
+        x * x / 4 + x * 96 - 3072
+    } else {
+        x * x / 16 + x * 480 - 199680
+    };
+
+    complexity
+        .checked_mul(max(iterations, U256::one()))
+        .map(|cost| cost / gas::MODEXP_DIVISOR_BYZANTIUM)
+        .unwrap_or(U256::MAX)
+}
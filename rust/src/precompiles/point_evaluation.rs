@@ -0,0 +1,67 @@
+use c_kzg::{Bytes32, Bytes48};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::ExecutionError,
+    gas::{self, Gas},
+};
+
+const INPUT_SIZE: usize = 192;
+
+/// Version byte of a KZG commitment hash (EIP-4844).
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// Number of field elements in a blob, as a 32-byte word.
+const FIELD_ELEMENTS_PER_BLOB: [u8; 32] = {
+    let mut bytes = [0u8; 32];
+    bytes[30] = 0x10;
+    bytes
+};
+
+/// Order of the BLS12-381 scalar field, as a 32-byte word.
+const BLS_MODULUS: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// Checks that the blob behind `versioned_hash` evaluates to `y` at `z`, given its KZG
+/// `commitment` and a `proof` (0x0a, EIP-4844). The input is versioned_hash ++ z ++ y ++
+/// commitment ++ proof, and the proof is checked against the mainnet trusted setup.
+pub fn point_evaluation(input: &[u8], gas: &mut Gas) -> Result<Vec<u8>, ExecutionError> {
+    gas.charge(gas::POINT_EVALUATION)?;
+
+    if input.len() != INPUT_SIZE {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let versioned_hash = &input[..32];
+    let commitment = &input[96..144];
+    if versioned_hash != kzg_to_versioned_hash(commitment) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let z = Bytes32::from_bytes(&input[32..64]);
+    let y = Bytes32::from_bytes(&input[64..96]);
+    let commitment = Bytes48::from_bytes(commitment);
+    let proof = Bytes48::from_bytes(&input[144..]);
+    let (Ok(z), Ok(y), Ok(commitment), Ok(proof)) = (z, y, commitment, proof) else {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    };
+
+    let verified = c_kzg::ethereum_kzg_settings(0).verify_kzg_proof(&commitment, &z, &y, &proof);
+    if !matches!(verified, Ok(true)) {
+        return Err(ExecutionError::InvalidPrecompileInput);
+    }
+
+    let mut output = FIELD_ELEMENTS_PER_BLOB.to_vec();
+    output.extend_from_slice(&BLS_MODULUS);
+    Ok(output)
+}
+
+/// Hash under which a blob is referenced by transactions: its commitment's SHA2-256 digest,
+/// with the first byte replaced by the version.
+fn kzg_to_versioned_hash(commitment: &[u8]) -> Vec<u8> {
+    let mut hash = Sha256::digest(commitment).to_vec();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
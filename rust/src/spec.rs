@@ -0,0 +1,35 @@
+use serde::Deserialize;
+
+/// Ethereum hardforks, in activation order. Each one keeps the rules of those before it
+/// except for the ones it replaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    /// EIP-150: repriced state accesses and the 63/64 rule for forwarded gas.
+    TangerineWhistle,
+    /// EIP-160 and EIP-161: EXP repricing and empty accounts.
+    SpuriousDragon,
+    Byzantium,
+    /// Same rules as Petersburg, which rolled back the SSTORE pricing of EIP-1283.
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    /// Also known as Paris.
+    Merge,
+    Shanghai,
+    #[default]
+    Cancun,
+    /// Cancun with the BLS12-381 precompiles of EIP-2537 and the blob base fee update fraction
+    /// of EIP-7691.
+    Prague,
+}
+
+impl SpecId {
+    /// Whether the rules introduced in `fork` apply.
+    pub fn enabled(self, fork: SpecId) -> bool {
+        self >= fork
+    }
+}
@@ -0,0 +1,58 @@
+use primitive_types::U256;
+
+use crate::error::ExecutionError;
+
+pub const STACK_LIMIT: usize = 1024;
+
+#[derive(Debug, Default)]
+pub struct Stack {
+    values: Vec<U256>,
+}
+
+impl Stack {
+    pub fn pop(&mut self) -> Result<U256, ExecutionError> {
+        self.values.pop().ok_or(ExecutionError::StackUnderflow)
+    }
+
+    pub fn push(&mut self, value: U256) -> Result<(), ExecutionError> {
+        if self.values.len() >= STACK_LIMIT {
+            return Err(ExecutionError::StackOverflow);
+        }
+
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Value `depth` items below the top of the stack, the top one being at depth 1.
+    pub fn peek(&self, depth: usize) -> Result<U256, ExecutionError> {
+        self.values
+            .len()
+            .checked_sub(depth)
+            .map(|index| self.values[index])
+            .ok_or(ExecutionError::StackUnderflow)
+    }
+
+    /// Swaps the top of the stack with the item `depth` places below it.
+    pub fn swap(&mut self, depth: usize) -> Result<(), ExecutionError> {
+        let len = self.values.len();
+        if depth >= len {
+            return Err(ExecutionError::StackUnderflow);
+        }
+
+        self.values.swap(len - 1, len - 1 - depth);
+        Ok(())
+    }
+
+    /// Items from the top down.
+    pub fn to_vec(&self) -> Vec<U256> {
+        self.values.iter().rev().copied().collect()
+    }
+
+    /// Consumes the stack, returning its items from the top down.
+    pub fn into_vec(self) -> Vec<U256> {
+        let mut values = self.values;
+        values.reverse();
+
+        values
+    }
+}
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use primitive_types::U256;
 use serde::{de::Visitor, Deserialize};
 
+use crate::spec::SpecId;
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct SerializedU256 {
     pub value: U256,
@@ -56,6 +58,8 @@ pub struct TxData {
     pub gasprice: Option<SerializedU256>,
     pub value: Option<SerializedU256>,
     pub data: Option<SerializedBytes>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Vec<SerializedU256>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
@@ -65,14 +69,75 @@ pub struct BlockData {
     pub timestamp: Option<SerializedU256>,
     pub number: Option<SerializedU256>,
     pub difficulty: Option<SerializedU256>,
+    pub prevrandao: Option<SerializedU256>,
+    pub excess_blob_gas: Option<SerializedU256>,
     pub gaslimit: Option<SerializedU256>,
     pub chainid: Option<SerializedU256>,
+    /// Hashes of recent blocks by number. Missing ones are derived from the number.
+    #[serde(default)]
+    pub hashes: HashMap<SerializedU256, SerializedU256>,
+}
+
+/// Lowest price of a unit of blob gas, in wei (EIP-4844).
+pub const MIN_BLOB_BASE_FEE: u64 = 1;
+/// Controls how fast the blob base fee reacts to the excess blob gas (EIP-4844).
+pub const BLOB_BASE_FEE_UPDATE_FRACTION: u64 = 3338477;
+/// Prague raises the blob target, so the fee reacts more slowly (EIP-7691).
+pub const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5007716;
+
+impl BlockData {
+    /// Randomness from the beacon chain. Blocks from before the merge report their difficulty
+    /// in its place.
+    pub fn prevrandao(&self) -> SerializedU256 {
+        self.prevrandao.or(self.difficulty).unwrap_or_default()
+    }
+
+    /// Price of a unit of blob gas, which grows exponentially with the excess blob gas.
+    pub fn blob_base_fee(&self, spec: SpecId) -> U256 {
+        let update_fraction = if spec.enabled(SpecId::Prague) {
+            BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE
+        } else {
+            BLOB_BASE_FEE_UPDATE_FRACTION
+        };
+
+        fake_exponential(
+            MIN_BLOB_BASE_FEE.into(),
+            self.excess_blob_gas.unwrap_or_default().into(),
+            update_fraction.into(),
+        )
+    }
+}
+
+/// Approximates factor * e ** (numerator / denominator) with integer math (EIP-4844).
+fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let mut output = U256::zero();
+    let mut accumulator = factor * denominator;
+    let mut i = U256::one();
+
+    while !accumulator.is_zero() {
+        // no one could ever pay a fee this large, so there is no point in computing it exactly
+        let (Some(sum), Some(product)) = (
+            output.checked_add(accumulator),
+            accumulator.checked_mul(numerator),
+        ) else {
+            return U256::MAX;
+        };
+
+        output = sum;
+        accumulator = product / (denominator * i);
+        i += U256::one();
+    }
+
+    output / denominator
 }
 
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
 pub struct ContractsStateDataEntry {
+    pub nonce: Option<SerializedU256>,
     pub balance: Option<SerializedU256>,
     pub code: Option<ContractsStateDataEntryCode>,
+    #[serde(default)]
+    pub storage: HashMap<SerializedU256, SerializedU256>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
@@ -84,15 +149,382 @@ impl ContractsStateDataEntryCode {
     pub fn len(&self) -> usize {
         self.bin.as_ref().map(|bin| bin.len()).unwrap_or_default()
     }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub type ContractsStateData = HashMap<SerializedU256, ContractsStateDataEntry>;
 
+/// Accounts and storage slots touched so far in the transaction (EIP-2929).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessList {
+    pub addresses: HashSet<SerializedU256>,
+    pub storage_keys: HashSet<(SerializedU256, U256)>,
+}
+
+/// A single reversible change to the world state, holding what is needed to undo it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JournalEntry {
+    AccountCreated(SerializedU256),
+    ContractCreated(SerializedU256),
+    SelfDestructScheduled(SerializedU256),
+    NonceChanged {
+        address: SerializedU256,
+        previous: Option<SerializedU256>,
+    },
+    BalanceChanged {
+        address: SerializedU256,
+        previous: Option<SerializedU256>,
+    },
+    CodeChanged {
+        address: SerializedU256,
+        previous: Option<ContractsStateDataEntryCode>,
+    },
+    StorageChanged {
+        address: SerializedU256,
+        key: U256,
+        previous: U256,
+    },
+    TransientStorageChanged {
+        address: SerializedU256,
+        key: U256,
+        previous: U256,
+    },
+    AddressWarmed(SerializedU256),
+    StorageKeyWarmed(SerializedU256, U256),
+    RefundChanged(u64),
+}
+
+/// Position in the journal to which the state can later be reverted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlockchainState {
     pub tx: TxData,
     pub block: BlockData,
     pub contracts_state: ContractsStateData,
+    pub access_list: AccessList,
+    pub original_storage: HashMap<(SerializedU256, U256), U256>,
+    /// Per-account storage that only lives as long as the transaction (EIP-1153).
+    pub transient_storage: HashMap<SerializedU256, HashMap<SerializedU256, SerializedU256>>,
+    /// Contracts deployed during the transaction, the only ones SELFDESTRUCT may delete (EIP-6780).
+    pub created_contracts: HashSet<SerializedU256>,
+    /// Accounts that self-destructed, deleted once the transaction is over.
+    pub selfdestructs: HashSet<SerializedU256>,
+    pub refund: u64,
+    journal: Vec<JournalEntry>,
+    open_checkpoints: usize,
+}
+
+impl BlockchainState {
+    /// Wraps the world state together with the transaction and block it is executed in.
+    pub fn new(tx: TxData, block: BlockData, contracts_state: ContractsStateData) -> Self {
+        BlockchainState {
+            tx,
+            block,
+            contracts_state,
+            access_list: AccessList::default(),
+            original_storage: HashMap::new(),
+            transient_storage: HashMap::new(),
+            created_contracts: HashSet::new(),
+            selfdestructs: HashSet::new(),
+            refund: 0,
+            journal: Vec::new(),
+            open_checkpoints: 0,
+        }
+    }
+
+    /// Opens a checkpoint: every change made from now on can be undone with [`Self::revert`].
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.open_checkpoints += 1;
+        Checkpoint(self.journal.len())
+    }
+
+    /// Keeps the changes made since `checkpoint`. They can still be undone by an outer revert.
+    pub fn commit(&mut self, _checkpoint: Checkpoint) {
+        self.open_checkpoints -= 1;
+        if self.open_checkpoints == 0 {
+            self.journal.clear();
+        }
+    }
+
+    /// Undoes, newest first, every change made since `checkpoint`.
+    pub fn revert(&mut self, checkpoint: Checkpoint) {
+        self.open_checkpoints -= 1;
+        let entries = self.journal.split_off(checkpoint.0);
+        for entry in entries.into_iter().rev() {
+            match entry {
+                JournalEntry::AccountCreated(address) => {
+                    self.contracts_state.remove(&address);
+                }
+                JournalEntry::ContractCreated(address) => {
+                    self.created_contracts.remove(&address);
+                }
+                JournalEntry::SelfDestructScheduled(address) => {
+                    self.selfdestructs.remove(&address);
+                }
+                JournalEntry::NonceChanged { address, previous } => {
+                    if let Some(entry) = self.contracts_state.get_mut(&address) {
+                        entry.nonce = previous;
+                    }
+                }
+                JournalEntry::BalanceChanged { address, previous } => {
+                    if let Some(entry) = self.contracts_state.get_mut(&address) {
+                        entry.balance = previous;
+                    }
+                }
+                JournalEntry::CodeChanged { address, previous } => {
+                    if let Some(entry) = self.contracts_state.get_mut(&address) {
+                        entry.code = previous;
+                    }
+                }
+                JournalEntry::StorageChanged {
+                    address,
+                    key,
+                    previous,
+                } => {
+                    if let Some(entry) = self.contracts_state.get_mut(&address) {
+                        write_slot(&mut entry.storage, key, previous);
+                    }
+                }
+                JournalEntry::TransientStorageChanged {
+                    address,
+                    key,
+                    previous,
+                } => {
+                    let storage = self.transient_storage.entry(address).or_default();
+                    write_slot(storage, key, previous);
+                }
+                JournalEntry::AddressWarmed(address) => {
+                    self.access_list.addresses.remove(&address);
+                }
+                JournalEntry::StorageKeyWarmed(address, key) => {
+                    self.access_list.storage_keys.remove(&(address, key));
+                }
+                JournalEntry::RefundChanged(previous) => self.refund = previous,
+            }
+        }
+    }
+
+    /// Marks `address` as accessed, returning whether this was its first (cold) access.
+    pub fn warm_address(&mut self, address: SerializedU256) -> bool {
+        let is_cold = self.access_list.addresses.insert(address);
+        if is_cold {
+            self.journal.push(JournalEntry::AddressWarmed(address));
+        }
+
+        is_cold
+    }
+
+    /// Marks the storage slot `key` of `address` as accessed, returning whether it was cold.
+    pub fn warm_storage_key(&mut self, address: SerializedU256, key: U256) -> bool {
+        let is_cold = self.access_list.storage_keys.insert((address, key));
+        if is_cold {
+            self.journal
+                .push(JournalEntry::StorageKeyWarmed(address, key));
+        }
+
+        is_cold
+    }
+
+    pub fn add_refund(&mut self, amount: u64) {
+        self.journal.push(JournalEntry::RefundChanged(self.refund));
+        self.refund += amount;
+    }
+
+    pub fn sub_refund(&mut self, amount: u64) {
+        self.journal.push(JournalEntry::RefundChanged(self.refund));
+        self.refund = self.refund.saturating_sub(amount);
+    }
+
+    /// Looks up `address`, creating an empty account (and journaling it) if there is none.
+    fn account_mut(&mut self, address: SerializedU256) -> &mut ContractsStateDataEntry {
+        if !self.contracts_state.contains_key(&address) {
+            self.journal.push(JournalEntry::AccountCreated(address));
+        }
+
+        self.contracts_state.entry(address).or_default()
+    }
+
+    pub fn nonce(&self, address: SerializedU256) -> U256 {
+        self.contracts_state
+            .get(&address)
+            .and_then(|entry| entry.nonce)
+            .unwrap_or_default()
+            .into()
+    }
+
+    pub fn set_nonce(&mut self, address: SerializedU256, nonce: U256) {
+        let entry = self.account_mut(address);
+        let previous = entry.nonce.replace(nonce.into());
+        self.journal
+            .push(JournalEntry::NonceChanged { address, previous });
+    }
+
+    pub fn balance(&self, address: SerializedU256) -> U256 {
+        self.contracts_state
+            .get(&address)
+            .and_then(|entry| entry.balance)
+            .unwrap_or_default()
+            .into()
+    }
+
+    pub fn set_balance(&mut self, address: SerializedU256, balance: U256) {
+        let entry = self.account_mut(address);
+        let previous = entry.balance.replace(balance.into());
+        self.journal
+            .push(JournalEntry::BalanceChanged { address, previous });
+    }
+
+    /// Moves `value` wei from `from` to `to`, or returns false if `from` cannot afford it.
+    pub fn transfer(&mut self, from: SerializedU256, to: SerializedU256, value: U256) -> bool {
+        let from_balance = self.balance(from);
+        if from_balance < value {
+            return false;
+        }
+
+        if value.is_zero() || from == to {
+            return true;
+        }
+
+        self.set_balance(from, from_balance - value);
+        let to_balance = self.balance(to);
+        self.set_balance(to, to_balance + value);
+        true
+    }
+
+    pub fn account_exists(&self, address: SerializedU256) -> bool {
+        self.contracts_state.contains_key(&address)
+    }
+
+    /// Whether `address` has no nonce, balance or code (EIP-161), which includes not existing.
+    pub fn is_empty_account(&self, address: SerializedU256) -> bool {
+        self.nonce(address).is_zero()
+            && self.balance(address).is_zero()
+            && self.code(address).is_empty()
+    }
+
+    pub fn code(&self, address: SerializedU256) -> Vec<u8> {
+        self.contracts_state
+            .get(&address)
+            .and_then(|entry| entry.code.clone())
+            .and_then(|code| code.bin)
+            .unwrap_or_default()
+            .into()
+    }
+
+    pub fn set_code(&mut self, address: SerializedU256, code: Vec<u8>) {
+        let entry = self.account_mut(address);
+        let previous = entry.code.replace(ContractsStateDataEntryCode {
+            bin: Some(code.into()),
+        });
+        self.journal
+            .push(JournalEntry::CodeChanged { address, previous });
+    }
+
+    /// Records that a contract is being deployed at `address` in this transaction.
+    pub fn mark_created(&mut self, address: SerializedU256) {
+        if self.created_contracts.insert(address) {
+            self.journal.push(JournalEntry::ContractCreated(address));
+        }
+    }
+
+    /// Schedules `address` for deletion at the end of the transaction, returning whether it
+    /// was not already.
+    pub fn schedule_selfdestruct(&mut self, address: SerializedU256) -> bool {
+        let is_new = self.selfdestructs.insert(address);
+        if is_new {
+            self.journal
+                .push(JournalEntry::SelfDestructScheduled(address));
+        }
+
+        is_new
+    }
+
+    /// Applies what only takes effect once the transaction is over: self-destructed accounts
+    /// are deleted, and transient storage, the access list and the refund counter are
+    /// discarded.
+    pub fn end_transaction(&mut self) {
+        for address in self.selfdestructs.drain() {
+            self.contracts_state.remove(&address);
+        }
+        self.created_contracts.clear();
+        self.transient_storage.clear();
+        self.access_list = AccessList::default();
+        self.original_storage.clear();
+        self.refund = 0;
+        self.journal.clear();
+    }
+
+    pub fn storage_value(&self, address: SerializedU256, key: U256) -> U256 {
+        self.contracts_state
+            .get(&address)
+            .and_then(|entry| entry.storage.get(&key.into()))
+            .copied()
+            .unwrap_or_default()
+            .into()
+    }
+
+    pub fn set_storage_value(&mut self, address: SerializedU256, key: U256, value: U256) {
+        let storage = &mut self.account_mut(address).storage;
+        let previous = write_slot(storage, key, value);
+        self.journal.push(JournalEntry::StorageChanged {
+            address,
+            key,
+            previous,
+        });
+    }
+
+    pub fn transient_storage_value(&self, address: SerializedU256, key: U256) -> U256 {
+        self.transient_storage
+            .get(&address)
+            .and_then(|storage| storage.get(&key.into()))
+            .copied()
+            .unwrap_or_default()
+            .into()
+    }
+
+    pub fn set_transient_storage_value(&mut self, address: SerializedU256, key: U256, value: U256) {
+        let storage = self.transient_storage.entry(address).or_default();
+        let previous = write_slot(storage, key, value);
+        self.journal.push(JournalEntry::TransientStorageChanged {
+            address,
+            key,
+            previous,
+        });
+    }
+
+    /// Value the slot held when the transaction started, given the one it holds right now.
+    pub fn original_storage_value(
+        &mut self,
+        address: SerializedU256,
+        key: U256,
+        current: U256,
+    ) -> U256 {
+        *self
+            .original_storage
+            .entry((address, key))
+            .or_insert(current)
+    }
+}
+
+/// Writes `value` to the slot `key`, dropping zeroed slots, and returns what it held before.
+fn write_slot(
+    storage: &mut HashMap<SerializedU256, SerializedU256>,
+    key: U256,
+    value: U256,
+) -> U256 {
+    let previous = if value.is_zero() {
+        storage.remove(&key.into())
+    } else {
+        storage.insert(key.into(), value.into())
+    };
+
+    previous.unwrap_or_default().into()
 }
 
 struct SerializedU256Visitor;
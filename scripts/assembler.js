@@ -47,10 +47,13 @@ const OPCODES = {
   TIMESTAMP: 0x42,
   NUMBER: 0x43,
   DIFFICULTY: 0x44,
+  PREVRANDAO: 0x44,
   GASLIMIT: 0x45,
   CHAINID: 0x46,
   SELFBALANCE: 0x47,
   BASEFEE: 0x48,
+  BLOBHASH: 0x49,
+  BLOBBASEFEE: 0x4a,
   POP: 0x50,
   MLOAD: 0x51,
   MSTORE: 0x52,
@@ -63,6 +66,9 @@ const OPCODES = {
   MSIZE: 0x59,
   GAS: 0x5a,
   JUMPDEST: 0x5b,
+  TLOAD: 0x5c,
+  TSTORE: 0x5d,
+  MCOPY: 0x5e,
   PUSH0: 0x5f,
   PUSH1: 0x60,
   PUSH2: 0x61,
@@ -30,7 +30,7 @@ function main() {
       return parseYamlBigInt(value);
     }
 
-    if (key === 'state') {
+    if (key === 'state' || key === 'hashes') {
       return Object.fromEntries(Object.entries(value).map(([address, account]) => [parseYamlBigInt(address), account]));
     }
 